@@ -2,12 +2,56 @@ var rust = require('../native');
 
 rust.init_rust();
 
+var ErrorCode = {
+  INVALID_LENGTH: 'ERR_INVALID_LENGTH',
+  INVALID_ARGUMENT: 'ERR_INVALID_ARGUMENT',
+  BAD_PASSWORD: 'ERR_BAD_PASSWORD',
+  DECODE: 'ERR_DECODE',
+  INSUFFICIENT_FUNDS: 'ERR_INSUFFICIENT_FUNDS',
+  OUTPUT_TOO_LARGE: 'ERR_OUTPUT_TOO_LARGE',
+  KEY_DESTROYED: 'ERR_KEY_DESTROYED',
+  MNEMONIC_WORD_COUNT: 'ERR_MNEMONIC_WORD_COUNT',
+  MNEMONIC_UNKNOWN_WORD: 'ERR_MNEMONIC_UNKNOWN_WORD',
+  MNEMONIC_CHECKSUM: 'ERR_MNEMONIC_CHECKSUM',
+  TX_TOO_LARGE: 'ERR_TX_TOO_LARGE',
+  INVALID_PATH: 'ERR_INVALID_PATH',
+  HARDENED_PUBLIC_DERIVATION: 'ERR_HARDENED_PUBLIC_DERIVATION',
+  WALLET: 'ERR_WALLET',
+  NATIVE: 'ERR_NATIVE'
+};
+
+// Failures are thrown natively with their error code
 function handleResultString(value) {
-  var data = JSON.parse(value);
-  if (data.failed) {
-    throw new Error("Error in: " + data.loc + ", message: " + data.msg);
-  }
-  return data.result;
+  return JSON.parse(value).result;
+}
+
+function resultCallback(resolve, reject, transform) {
+  return function (err, value) {
+    if (err) {
+      return reject(err);
+    }
+    try {
+      var result = handleResultString(value);
+      resolve(transform ? transform(result) : result);
+    } catch (e) {
+      reject(e);
+    }
+  };
+}
+
+// Settles a promise with the result of a native background task
+function taskCallback(resolve, reject) {
+  return function (err, value) {
+    if (err) {
+      return reject(err);
+    }
+    resolve(value);
+  };
+}
+
+function decodeTransaction(response) {
+  response['cbor_encoded_tx'] = Buffer.from(response['cbor_encoded_tx']);
+  return response;
 }
 
 var PasswordProtect = {
@@ -15,24 +59,35 @@ var PasswordProtect = {
   decryptWithPassword: rust.password_protect_decrypt_with_password
 };
 
+// Checkers are native objects, the HD payload key never leaves Rust memory.
+// Breaking: newChecker returns a native object and only takes the root XPrv as a
+// Buffer, it used to JSON encode its argument so hex strings were accepted too.
+// JSON checkers of earlier versions are still accepted, but deprecated.
 var RandomAddressChecker = {
-  newChecker: function (xprv) {
-    return handleResultString(
-      rust.random_checker_new_checker(JSON.stringify(xprv))
-    );
-  },
-  newCheckerFromMnemonics: function (mnemonics) {
-    return handleResultString(
-      rust.random_checker_new_checker_from_mnemonics(JSON.stringify(mnemonics))
-    );
-  },
+  newChecker: rust.random_checker_new_checker, // xprv: Buffer
+  newCheckerFromMnemonics: rust.random_checker_new_checker_from_mnemonics,
   checkAddresses: function (checker, addresses) {
+    if (checker instanceof rust.RandomAddressChecker) {
+      return checker.check(addresses);
+    }
     return handleResultString(
       rust.random_checker_check_addresses(
         JSON.stringify({checker: checker, addresses: addresses})
       )
     );
-  }
+  },
+  checkAddressesAsync: function (checker, addresses) {
+    return new Promise(function (resolve, reject) {
+      if (checker instanceof rust.RandomAddressChecker) {
+        return checker.checkAsync(addresses, taskCallback(resolve, reject));
+      }
+      rust.random_checker_check_addresses_async(
+        JSON.stringify({checker: checker, addresses: addresses}),
+        resultCallback(resolve, reject)
+      );
+    });
+  },
+  RandomAddressChecker: rust.RandomAddressChecker
 };
 
 var HdWallet = {
@@ -41,7 +96,139 @@ var HdWallet = {
   toPublic: rust.hdwallet_to_public,
   derivePrivate: rust.hdwallet_derive_private,
   derivePublic: rust.hdwallet_derive_public,
-  sign: rust.hdwallet_sign
+  derivePrivatePath: rust.hdwallet_derive_private_path,
+  derivePublicPath: rust.hdwallet_derive_public_path,
+  sign: rust.hdwallet_sign,
+  verify: rust.hdwallet_verify,
+  verifyPublicKey: rust.hdwallet_verify_public_key,
+  privateKeyFromEnhancedEntropy: rust.private_key_from_enhanced_entropy,
+  privateKeyFromSeed: rust.private_key_from_seed,
+  privateKeyFromXPrv: rust.private_key_from_xprv,
+  PrivateKey: rust.PrivateKey
+};
+
+var CHAINS = { External: 0, Internal: 1 };
+
+// Splits the packed addresses of a derived range, each one is prefixed with its length
+function unpackAddresses(packed) {
+  var addresses = [];
+  var offset = 0;
+  while (offset < packed.length) {
+    var size = packed[offset];
+    addresses.push(packed.toString('ascii', offset + 1, offset + 1 + size));
+    offset += 1 + size;
+  }
+  return addresses;
+}
+
+var Address = {
+  deriveRange: function (xpub, type, start, count, protocolMagic, parallel) {
+    return rust.address_derive_range(xpub, CHAINS[type], start, count, protocolMagic, !!parallel);
+  },
+  deriveRangeAsync: function (xpub, type, start, count, protocolMagic, parallel) {
+    return new Promise(function (resolve, reject) {
+      rust.address_derive_range_async(
+        xpub, CHAINS[type], start, count, protocolMagic, !!parallel,
+        function (err, range) { err ? reject(err) : resolve(range); }
+      );
+    });
+  },
+  unpackAddresses: unpackAddresses,
+  decode: rust.address_decode,
+  validate: rust.address_validate
+};
+
+// CIP-1852 chains of an account
+var ROLES = { External: 0, Internal: 1, Staking: 2 };
+
+// Shelley addresses of an account are staked with the first key of its staking chain
+var Shelley = {
+  NETWORK_TESTNET: 0,
+  NETWORK_MAINNET: 1,
+  newAccount: function (wallet, account) {
+    var xpub = rust.shelley_account_xpub(Buffer.from(wallet.root_cached_key, 'hex'), account);
+    var stakeKey = rust.shelley_stake_key(xpub);
+    return {
+      root_cached_key: xpub.toString('hex'),
+      derivation_scheme: wallet.derivation_scheme,
+      purpose: 1852,
+      account: account,
+      stake_key: stakeKey.xpub.toString('hex'),
+      stake_key_hash: stakeKey.hash.toString('hex')
+    };
+  },
+  chainKey: function (account, role, index) {
+    return rust.hdwallet_derive_public_path(
+      Buffer.from(account.root_cached_key, 'hex'), ROLES[role] + '/' + index
+    );
+  },
+  baseAddress: rust.shelley_base_address,
+  pointerAddress: function (xpub, pointer, networkId) {
+    return rust.shelley_pointer_address(xpub, pointer.slot, pointer.txIndex, pointer.certIndex, networkId);
+  },
+  enterpriseAddress: rust.shelley_enterprise_address,
+  rewardAddress: rust.shelley_reward_address,
+  decodeAddress: rust.shelley_decode_address,
+  generateAddresses: function (account, type, indices, networkId) {
+    return rust.shelley_account_addresses(
+      Buffer.from(account.root_cached_key, 'hex'), CHAINS[type], indices, networkId, false
+    );
+  },
+  generateEnterpriseAddresses: function (account, type, indices, networkId) {
+    return rust.shelley_account_addresses(
+      Buffer.from(account.root_cached_key, 'hex'), CHAINS[type], indices, networkId, true
+    );
+  },
+  // options: { ttl, network_id, certificates, withdrawals, protocol_params }
+  spend: function (wallet, inputs, outputs, change_addr, options) {
+    var input = Object.assign({
+      wallet: wallet, inputs: inputs, outputs: outputs, change_addr: change_addr
+    }, options);
+    return rust.shelley_tx_spend(JSON.stringify(input));
+  },
+  // Online half: needs only the account created by newAccount
+  buildUnsigned: function (account, inputs, outputs, change_addr, options) {
+    var input = Object.assign({
+      account: { root_cached_key: account.root_cached_key, account: account.account },
+      inputs: inputs, outputs: outputs, change_addr: change_addr
+    }, options);
+    return rust.shelley_tx_build_unsigned(JSON.stringify(input));
+  },
+  // Offline half: witnesses the body with the root key
+  sign: rust.shelley_tx_sign
+};
+
+// Signed Byron or Shelley transactions
+var Transaction = {
+  decode: rust.transaction_decode,
+  // utxos: outputs spent by the inputs, in input order. config: the wallet config
+  verify: function (tx, utxos, config) {
+    return rust.transaction_verify(tx, JSON.stringify(utxos), config.protocol_magic);
+  }
+};
+
+// Passing no language means English when creating a phrase and auto detection when reading one
+var Mnemonic = {
+  LANGUAGES: Object.freeze([
+    'english', 'japanese', 'spanish', 'french', 'italian', 'korean',
+    'chinese_simplified', 'chinese_traditional'
+  ]),
+  generate: function (words, language) {
+    return rust.mnemonic_generate(words, language || '');
+  },
+  validate: function (phrase, language) {
+    return rust.mnemonic_validate(phrase, language || '');
+  },
+  toEntropy: function (phrase, language) {
+    return rust.mnemonic_to_entropy(phrase, language || '');
+  },
+  fromEntropy: function (entropy, language) {
+    return rust.mnemonic_from_entropy(entropy, language || '');
+  },
+  suggest: function (prefix, language) {
+    return rust.mnemonic_suggest(prefix, language || '');
+  },
+  detectLanguage: rust.mnemonic_detect_language
 };
 
 var Wallet = {
@@ -66,35 +253,111 @@ var Wallet = {
       )
     )
   },
+  generateAddressesAsync: function (account, type, indices, protocolMagic) {
+    return new Promise(function (resolve, reject) {
+      rust.wallet_generate_addresses_async(
+        JSON.stringify({ account: account, address_type: type, indices: indices, protocol_magic: protocolMagic }),
+        indices.length,
+        resultCallback(resolve, reject)
+      );
+    });
+  },
   checkAddress: function (address) { // base58
     return handleResultString(
       rust.wallet_check_address(address)
     )
   },
-  spend: function (wallet, inputs, outputs, change_addr) {
-    var input = {
-      wallet: wallet, inputs: inputs,
-      outputs: outputs, change_addr: change_addr
-    };
-    var response = handleResultString(
-      rust.wallet_spend(JSON.stringify(input), inputs.length, outputs.length)
-    );
-    response['cbor_encoded_tx'] = Buffer.from(response['cbor_encoded_tx']);
-    return response;
+  // Daedalus random address, at a random [account, index] when none is given
+  generateRandomAddress: function (wallet, addressing) {
+    return rust.random_address_generate(JSON.stringify({ wallet: wallet, addressing: addressing }));
+  },
+  // Without protocol parameters the mainnet fee policy is used.
+  // selectionSeed makes the RandomImprove policy reproducible.
+  spend: function (wallet, inputs, outputs, change_addr, protocolParams, selectionSeed) {
+    return rust.byron_tx_spend(JSON.stringify({
+      wallet: wallet, inputs: inputs, outputs: outputs, change_addr: change_addr,
+      protocol_params: protocolParams, selection_seed: selectionSeed
+    }));
+  },
+  spendAsync: function (wallet, inputs, outputs, change_addr, protocolParams, selectionSeed) {
+    return new Promise(function (resolve, reject) {
+      rust.byron_tx_spend_async(JSON.stringify({
+        wallet: wallet, inputs: inputs, outputs: outputs, change_addr: change_addr,
+        protocol_params: protocolParams, selection_seed: selectionSeed
+      }), taskCallback(resolve, reject));
+    });
   },
-  move: function (wallet, inputs, output) {
-    var response =  handleResultString(
+  // Fee of the transaction spend would build, without the wallet keys
+  estimateFee: function (inputs, outputs, change_addr, protocolParams, selectionPolicy, selectionSeed) {
+    return rust.byron_tx_estimate_fee(JSON.stringify({
+      inputs: inputs, outputs: outputs, change_addr: change_addr, protocol_params: protocolParams,
+      selection_policy: selectionPolicy, selection_seed: selectionSeed
+    }));
+  },
+  // Send all: spends every input to output, the fee being deducted from it
+  drain: function (wallet, inputs, output, protocolParams) {
+    return rust.byron_tx_drain(JSON.stringify({
+      wallet: wallet, inputs: inputs, output: output, protocol_params: protocolParams
+    }));
+  },
+  drainAsync: function (wallet, inputs, output, protocolParams) {
+    return new Promise(function (resolve, reject) {
+      rust.byron_tx_drain_async(JSON.stringify({
+        wallet: wallet, inputs: inputs, output: output, protocol_params: protocolParams
+      }), taskCallback(resolve, reject));
+    });
+  },
+  // Moves Byron funds, of BIP44 or random addresses, to a Shelley address.
+  // Without protocol parameters the Shelley mainnet ones are used.
+  migrate: function (wallet, inputs, output, ttl, protocolParams) {
+    return rust.migration_migrate(JSON.stringify({
+      wallet: wallet, inputs: inputs, output: output, ttl: ttl, protocol_params: protocolParams
+    }));
+  },
+  migrateAsync: function (wallet, inputs, output, ttl, protocolParams) {
+    return new Promise(function (resolve, reject) {
+      rust.migration_migrate_async(JSON.stringify({
+        wallet: wallet, inputs: inputs, output: output, ttl: ttl, protocol_params: protocolParams
+      }), taskCallback(resolve, reject));
+    });
+  },
+  move: function (wallet, inputs, output, protocolParams) {
+    if (protocolParams) {
+      return rust.byron_tx_move(JSON.stringify({
+        wallet: wallet, inputs: inputs, output: output, protocol_params: protocolParams
+      }));
+    }
+    return decodeTransaction(handleResultString(
       rust.wallet_move(
         JSON.stringify({ wallet: wallet, inputs: inputs, output: output }),
         inputs.length
       )
-    );
-    response['cbor_encoded_tx'] = Buffer.from(response['cbor_encoded_tx']);
-    return response;
+    ));
+  },
+  moveAsync: function (wallet, inputs, output, protocolParams) {
+    if (protocolParams) {
+      return new Promise(function (resolve, reject) {
+        rust.byron_tx_move_async(JSON.stringify({
+          wallet: wallet, inputs: inputs, output: output, protocol_params: protocolParams
+        }), taskCallback(resolve, reject));
+      });
+    }
+    return new Promise(function (resolve, reject) {
+      rust.wallet_move_async(
+        JSON.stringify({ wallet: wallet, inputs: inputs, output: output }),
+        inputs.length,
+        resultCallback(resolve, reject, decodeTransaction)
+      );
+    });
   }
 };
 
+exports.ErrorCode = Object.freeze(ErrorCode);
 exports.PasswordProtect = Object.freeze(PasswordProtect);
 exports.RandomAddressChecker = Object.freeze(RandomAddressChecker);
 exports.HdWallet = Object.freeze(HdWallet);
+exports.Mnemonic = Object.freeze(Mnemonic);
+exports.Address = Object.freeze(Address);
+exports.Shelley = Object.freeze(Shelley);
+exports.Transaction = Object.freeze(Transaction);
 exports.Wallet = Object.freeze(Wallet);
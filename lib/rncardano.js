@@ -27,9 +27,7 @@ var RandomAddressChecker = {
     });
   },
   checkAddresses: function (checker, addresses) {
-    return Promise.resolve().then(function() {
-      return node.RandomAddressChecker.checkAddresses(checker, addresses);
-    });
+    return node.RandomAddressChecker.checkAddressesAsync(checker, addresses);
   }
 };
 
@@ -61,10 +59,198 @@ var HdWallet = {
       return node.HdWallet.derivePublic(Buffer.from(xpub, 'hex'), index).toString('hex');
     });
   },
+  derivePrivatePath: function (xprv, path) {
+    return Promise.resolve().then(function() {
+      return node.HdWallet.derivePrivatePath(Buffer.from(xprv, 'hex'), path).toString('hex');
+    });
+  },
+  derivePublicPath: function (xpub, path) {
+    return Promise.resolve().then(function() {
+      return node.HdWallet.derivePublicPath(Buffer.from(xpub, 'hex'), path).toString('hex');
+    });
+  },
   sign: function (xprv, data) {
     return Promise.resolve().then(function() {
       return node.HdWallet.sign(Buffer.from(xprv, 'hex'), Buffer.from(data, 'hex')).toString('hex');
     });
+  },
+  verify: function (xpub, data, signature) {
+    return Promise.resolve().then(function() {
+      return node.HdWallet.verify(
+        Buffer.from(xpub, 'hex'), Buffer.from(data, 'hex'), Buffer.from(signature, 'hex')
+      );
+    });
+  },
+  verifyPublicKey: function (publicKey, data, signature) {
+    return Promise.resolve().then(function() {
+      return node.HdWallet.verifyPublicKey(
+        Buffer.from(publicKey, 'hex'), Buffer.from(data, 'hex'), Buffer.from(signature, 'hex')
+      );
+    });
+  }
+};
+
+var Address = {
+  deriveRange: function (xpub, type, start, count, protocolMagic) {
+    return node.Address.deriveRangeAsync(
+      Buffer.from(xpub, 'hex'), type, start, count, protocolMagic, true
+    ).then(function(range) {
+      var xpubs = [];
+      for (var offset = 0; offset < range.xpubs.length; offset += 64) {
+        xpubs.push(range.xpubs.toString('hex', offset, offset + 64));
+      }
+      return { xpubs: xpubs, addresses: node.Address.unpackAddresses(range.addresses) };
+    });
+  },
+  decode: function (address) {
+    return Promise.resolve().then(function() {
+      var decoded = node.Address.decode(address);
+      decoded.root = decoded.root.toString('hex');
+      return decoded;
+    });
+  },
+  validate: function (address, protocolMagic) {
+    return Promise.resolve().then(function() {
+      return node.Address.validate(address, protocolMagic);
+    });
+  }
+};
+
+function credentialToHex(credential) {
+  if (credential) {
+    credential.hash = credential.hash.toString('hex');
+  }
+}
+
+var Shelley = {
+  NETWORK_TESTNET: node.Shelley.NETWORK_TESTNET,
+  NETWORK_MAINNET: node.Shelley.NETWORK_MAINNET,
+  newAccount: function (wallet, account) {
+    return Promise.resolve().then(function() {
+      return node.Shelley.newAccount(wallet, account);
+    });
+  },
+  chainKey: function (account, role, index) {
+    return Promise.resolve().then(function() {
+      return node.Shelley.chainKey(account, role, index).toString('hex');
+    });
+  },
+  baseAddress: function (paymentXPub, stakeXPub, networkId) {
+    return Promise.resolve().then(function() {
+      return node.Shelley.baseAddress(
+        Buffer.from(paymentXPub, 'hex'), Buffer.from(stakeXPub, 'hex'), networkId
+      );
+    });
+  },
+  pointerAddress: function (paymentXPub, pointer, networkId) {
+    return Promise.resolve().then(function() {
+      return node.Shelley.pointerAddress(Buffer.from(paymentXPub, 'hex'), pointer, networkId);
+    });
+  },
+  enterpriseAddress: function (paymentXPub, networkId) {
+    return Promise.resolve().then(function() {
+      return node.Shelley.enterpriseAddress(Buffer.from(paymentXPub, 'hex'), networkId);
+    });
+  },
+  rewardAddress: function (stakeXPub, networkId) {
+    return Promise.resolve().then(function() {
+      return node.Shelley.rewardAddress(Buffer.from(stakeXPub, 'hex'), networkId);
+    });
+  },
+  decodeAddress: function (address) {
+    return Promise.resolve().then(function() {
+      var decoded = node.Shelley.decodeAddress(address);
+      credentialToHex(decoded.payment);
+      credentialToHex(decoded.stake);
+      return decoded;
+    });
+  },
+  generateAddresses: function (account, type, indices, networkId) {
+    return Promise.resolve().then(function() {
+      return node.Shelley.generateAddresses(account, type, indices, networkId);
+    });
+  },
+  generateEnterpriseAddresses: function (account, type, indices, networkId) {
+    return Promise.resolve().then(function() {
+      return node.Shelley.generateEnterpriseAddresses(account, type, indices, networkId);
+    });
+  },
+  spend: function (wallet, inputs, outputs, change_addr, options) {
+    return Promise.resolve().then(function() {
+      var response = node.Shelley.spend(wallet, inputs, outputs, change_addr, options);
+      response['cbor_encoded_tx'] = response['cbor_encoded_tx'].toString('hex');
+      return response;
+    });
+  },
+  buildUnsigned: function (account, inputs, outputs, change_addr, options) {
+    return Promise.resolve().then(function() {
+      var response = node.Shelley.buildUnsigned(account, inputs, outputs, change_addr, options);
+      response['body'] = response['body'].toString('hex');
+      return response;
+    });
+  },
+  sign: function (xprv, body, paths) {
+    return Promise.resolve().then(function() {
+      return node.Shelley.sign(Buffer.from(xprv, 'hex'), Buffer.from(body, 'hex'), paths).toString('hex');
+    });
+  }
+};
+
+var Transaction = {
+  decode: function (tx) {
+    return Promise.resolve().then(function() {
+      var decoded = node.Transaction.decode(Buffer.from(tx, 'hex'));
+      if (decoded.attributes) {
+        decoded.attributes = decoded.attributes.toString('hex');
+      }
+      decoded.witnesses.forEach(function (witness) {
+        ['public_key', 'signature', 'chain_code', 'attributes'].forEach(function (key) {
+          if (witness[key]) {
+            witness[key] = witness[key].toString('hex');
+          }
+        });
+      });
+      return decoded;
+    });
+  },
+  verify: function (tx, utxos, config) {
+    return Promise.resolve().then(function() {
+      return node.Transaction.verify(Buffer.from(tx, 'hex'), utxos, config);
+    });
+  }
+};
+
+var Mnemonic = {
+  LANGUAGES: node.Mnemonic.LANGUAGES,
+  generate: function (words, language) {
+    return Promise.resolve().then(function() {
+      return node.Mnemonic.generate(words, language);
+    });
+  },
+  validate: function (phrase, language) {
+    return Promise.resolve().then(function() {
+      return node.Mnemonic.validate(phrase, language);
+    });
+  },
+  toEntropy: function (phrase, language) {
+    return Promise.resolve().then(function() {
+      return node.Mnemonic.toEntropy(phrase, language).toString('hex');
+    });
+  },
+  fromEntropy: function (entropy, language) {
+    return Promise.resolve().then(function() {
+      return node.Mnemonic.fromEntropy(Buffer.from(entropy, 'hex'), language);
+    });
+  },
+  suggest: function (prefix, language) {
+    return Promise.resolve().then(function() {
+      return node.Mnemonic.suggest(prefix, language);
+    });
+  },
+  detectLanguage: function (phrase) {
+    return Promise.resolve().then(function() {
+      return node.Mnemonic.detectLanguage(phrase);
+    });
   }
 };
 
@@ -85,25 +271,45 @@ var Wallet = {
     });
   },
   generateAddresses: function (account, type, indices, protocolMagic) {
-    return Promise.resolve().then(function() {
-      return node.Wallet.generateAddresses(account, type, indices, protocolMagic);
-    });
+    return node.Wallet.generateAddressesAsync(account, type, indices, protocolMagic);
   },
   checkAddress: function (address) {
     return Promise.resolve().then(function() {
       return node.Wallet.checkAddress(address);
     });
   },
-  spend: function (wallet, inputs, outputs, change_addr) {
+  generateRandomAddress: function (wallet, addressing) {
     return Promise.resolve().then(function() {
-      var response = node.Wallet.spend(wallet, inputs, outputs, change_addr);
+      return node.Wallet.generateRandomAddress(wallet, addressing);
+    });
+  },
+  spend: function (wallet, inputs, outputs, change_addr, protocolParams, selectionSeed) {
+    return node.Wallet.spendAsync(wallet, inputs, outputs, change_addr, protocolParams, selectionSeed).then(function(response) {
       response['cbor_encoded_tx'] = response['cbor_encoded_tx'].toString('hex');
       return response;
     });
   },
-  move: function (wallet, inputs, output) {
+  estimateFee: function (inputs, outputs, change_addr, protocolParams, selectionPolicy, selectionSeed) {
     return Promise.resolve().then(function() {
-      var response = node.Wallet.move(wallet, inputs, output);
+      return node.Wallet.estimateFee(inputs, outputs, change_addr, protocolParams, selectionPolicy, selectionSeed);
+    });
+  },
+  drain: function (wallet, inputs, output, protocolParams) {
+    return node.Wallet.drainAsync(wallet, inputs, output, protocolParams).then(function(response) {
+      response['cbor_encoded_tx'] = response['cbor_encoded_tx'].toString('hex');
+      return response;
+    });
+  },
+  migrate: function (wallet, inputs, output, ttl, protocolParams) {
+    return node.Wallet.migrateAsync(wallet, inputs, output, ttl, protocolParams).then(function(response) {
+      response.transactions.forEach(function (transaction) {
+        transaction['cbor_encoded_tx'] = transaction['cbor_encoded_tx'].toString('hex');
+      });
+      return response;
+    });
+  },
+  move: function (wallet, inputs, output, protocolParams) {
+    return node.Wallet.moveAsync(wallet, inputs, output, protocolParams).then(function(response) {
       response['cbor_encoded_tx'] = response['cbor_encoded_tx'].toString('hex');
       return response;
     });
@@ -113,4 +319,8 @@ var Wallet = {
 exports.PasswordProtect = Object.freeze(PasswordProtect);
 exports.RandomAddressChecker = Object.freeze(RandomAddressChecker);
 exports.HdWallet = Object.freeze(HdWallet);
+exports.Mnemonic = Object.freeze(Mnemonic);
+exports.Address = Object.freeze(Address);
+exports.Shelley = Object.freeze(Shelley);
+exports.Transaction = Object.freeze(Transaction);
 exports.Wallet = Object.freeze(Wallet);
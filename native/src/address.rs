@@ -0,0 +1,480 @@
+use neon::prelude::*;
+use exception::*;
+use hdwallet::{derive_public_indices, HARDENED_INDEX};
+use wallet_wasm;
+use cardano::address::ExtendedAddr;
+use cardano::config::{NetworkMagic, ProtocolMagic};
+use cardano::hdwallet::{self, XPub};
+use cardano::util::base58;
+use cbor;
+use cryptoxide::blake2b::Blake2b;
+use cryptoxide::digest::Digest;
+use cryptoxide::sha3::Sha3;
+use shelley;
+use bech32;
+use num_cpus;
+use std::cmp;
+use std::thread;
+
+// Smallest range worth a thread of its own
+const MIN_PARALLEL_RANGE: u32 = 256;
+
+// Largest range derived at once, the buffers of a range are allocated upfront
+pub const MAX_RANGE_COUNT: u32 = 100_000;
+
+pub fn u32_argument(value: f64, field: &'static str) -> Result<u32> {
+  if value.fract() != 0.0 || value < 0.0 || value > u32::max_value() as f64 {
+    return Err(Error::InvalidArgument(format!("Wrong {} {} should be a 32 bit unsigned integer", field, value)));
+  }
+  Ok(value as u32)
+}
+
+pub fn network_magic(protocol_magic: u32) -> NetworkMagic {
+  NetworkMagic::from(ProtocolMagic::from(protocol_magic))
+}
+
+// Base58 bootstrap era address of a public key, without HD payload
+pub fn byron_address(xpub: &[u8], magic: NetworkMagic) -> Result<String> {
+  let xpub = XPub::from_slice(xpub).map_err(|err| Error::Decode(format!("Invalid XPub: {:?}", err)))?;
+  Ok(format!("{}", ExtendedAddr::new_simple(xpub, magic)))
+}
+
+// Mainnet addresses carry no protocol magic attribute
+pub const MAINNET_PROTOCOL_MAGIC: u32 = 764824073;
+
+const ATTRIBUTE_HD_PAYLOAD: u64 = 1;
+const ATTRIBUTE_PROTOCOL_MAGIC: u64 = 2;
+const ROOT_SIZE: usize = 28;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ByronAddressType {
+  PubKey,
+  Script,
+  Redeem
+}
+
+impl ByronAddressType {
+  pub fn name(&self) -> &'static str {
+    match *self {
+      ByronAddressType::PubKey => "pubkey",
+      ByronAddressType::Script => "script",
+      ByronAddressType::Redeem => "redeem"
+    }
+  }
+
+  // Also the tag of the matching spending data
+  fn code(&self) -> u64 {
+    match *self {
+      ByronAddressType::PubKey => 0,
+      ByronAddressType::Script => 1,
+      ByronAddressType::Redeem => 2
+    }
+  }
+}
+
+// Fields of `[#6.24(bytes .cbor [root, attributes, type]), crc32]`
+pub struct ByronAddress {
+  pub root: Vec<u8>,
+  pub addr_type: ByronAddressType,
+  pub protocol_magic: Option<u32>,
+  pub hd_payload: Option<Vec<u8>>,
+  // Encoded attributes map, part of the hashed root
+  pub attributes: Vec<u8>,
+  pub crc: u32,
+  pub crc_valid: bool
+}
+
+pub fn decode_base58(address: &str) -> Result<Vec<u8>> {
+  base58::decode(address).map_err(|err| Error::Decode(format!("Couldn't decode base58: {:?}", err)))
+}
+
+// ExtendedAddr::from_bytes rejects a wrong CRC and re-encodes the attributes,
+// we report the CRC and need the attributes as they were hashed into the root.
+pub fn decode_byron(bytes: &[u8]) -> Result<ByronAddress> {
+  let mut reader = cbor::reader(bytes);
+  reader.tuple(2, "address")?;
+  let payload = cbor::encoded_cbor(&mut reader)?;
+  let crc = reader.unsigned_integer()?;
+  cbor::finish(&reader)?;
+  if crc > u32::max_value() as u64 {
+    return Err(Error::Decode(format!("Address CRC {} is larger than 32 bits", crc)));
+  }
+
+  let mut reader = cbor::reader(&payload);
+  reader.tuple(3, "address payload")?;
+  let root = reader.bytes()?;
+  if root.len() != ROOT_SIZE {
+    return Err(Error::InvalidLength { field: "address root", actual: root.len(), expected: ROOT_SIZE });
+  }
+
+  let mut protocol_magic = None;
+  let mut hd_payload = None;
+  let attributes_start = cbor::position(&reader);
+  let attributes = reader.map()?;
+  let mut index = 0;
+  while cbor::has_next(&mut reader, attributes, index)? {
+    match reader.unsigned_integer()? {
+      ATTRIBUTE_HD_PAYLOAD => hd_payload = Some(cbor::reader(&reader.bytes()?).bytes()?),
+      ATTRIBUTE_PROTOCOL_MAGIC => {
+        let magic = cbor::reader(&reader.bytes()?).unsigned_integer()?;
+        if magic > u32::max_value() as u64 {
+          return Err(Error::Decode(format!("Protocol magic {} is larger than 32 bits", magic)));
+        }
+        protocol_magic = Some(magic as u32);
+      },
+      _ => cbor::skip(&mut reader)?
+    }
+    index += 1;
+  }
+  let attributes = payload[attributes_start..cbor::position(&reader)].to_vec();
+
+  let addr_type = match reader.unsigned_integer()? {
+    0 => ByronAddressType::PubKey,
+    1 => ByronAddressType::Script,
+    2 => ByronAddressType::Redeem,
+    other => return Err(Error::Decode(format!("Unknown address type {}", other)))
+  };
+  cbor::finish(&reader)?;
+
+  Ok(ByronAddress {
+    root: root, addr_type: addr_type, protocol_magic: protocol_magic, hd_payload: hd_payload,
+    attributes: attributes, crc: crc as u32, crc_valid: cbor::crc32(&payload) == crc as u32
+  })
+}
+
+// blake2b-224(sha3-256([type, [type, key], attributes])), the key is an XPub
+// for public key addresses and an Ed25519 key for redeem ones
+pub fn byron_root(addr_type: ByronAddressType, public_key: &[u8], attributes: &[u8]) -> Vec<u8> {
+  let mut writer = cbor::Writer::new();
+  writer.array(3).unsigned(addr_type.code())
+    .array(2).unsigned(addr_type.code()).bytes(public_key)
+    .raw(attributes);
+  let mut sha3 = [0 as u8; 32];
+  let mut hasher = Sha3::sha3_256();
+  hasher.input(&writer.into_bytes());
+  hasher.result(&mut sha3);
+  let mut root = vec![0; ROOT_SIZE];
+  let mut hasher = Blake2b::new(ROOT_SIZE);
+  hasher.input(&sha3);
+  hasher.result(&mut root);
+  root
+}
+
+pub enum Verdict {
+  Valid,
+  WrongNetwork,
+  BadChecksum,
+  UnsupportedEra,
+  Malformed(Error)
+}
+
+impl Verdict {
+  pub fn name(&self) -> &'static str {
+    match *self {
+      Verdict::Valid => "valid",
+      Verdict::WrongNetwork => "wrong_network",
+      Verdict::BadChecksum => "bad_checksum",
+      Verdict::UnsupportedEra => "unsupported_era",
+      Verdict::Malformed(_) => "malformed"
+    }
+  }
+}
+
+// Shelley addresses are bech32 strings with these human readable parts
+pub fn is_bech32_era(address: &str) -> bool {
+  let lowercase = address.to_lowercase();
+  ["addr1", "addr_test1", "stake1", "stake_test1"].iter().any(|prefix| lowercase.starts_with(prefix))
+}
+
+pub struct Validation {
+  pub verdict: Verdict,
+  // Network of the address when it could be decoded
+  pub protocol_magic: Option<u32>,
+  pub network_id: Option<u8>
+}
+
+impl Validation {
+  fn new(verdict: Verdict) -> Self {
+    Validation { verdict: verdict, protocol_magic: None, network_id: None }
+  }
+}
+
+fn validate_byron(address: &str, protocol_magic: u32) -> Validation {
+  let bytes = match decode_base58(address) {
+    Ok(bytes) => bytes,
+    Err(err) => return Validation::new(Verdict::Malformed(err))
+  };
+  let decoded = match decode_byron(&bytes) {
+    Ok(decoded) => decoded,
+    Err(err) => return Validation::new(Verdict::Malformed(err))
+  };
+  let address_magic = decoded.protocol_magic.unwrap_or(MAINNET_PROTOCOL_MAGIC);
+  let verdict = if !decoded.crc_valid {
+    Verdict::BadChecksum
+  } else if address_magic != protocol_magic {
+    Verdict::WrongNetwork
+  } else {
+    Verdict::Valid
+  };
+  Validation { verdict: verdict, protocol_magic: Some(address_magic), network_id: None }
+}
+
+fn validate_shelley(address: &str, protocol_magic: u32) -> Validation {
+  let (hrp, bytes) = match shelley::decode_bech32(address) {
+    Ok(decoded) => decoded,
+    Err(bech32::Error::InvalidChecksum) => return Validation::new(Verdict::BadChecksum),
+    Err(err) => return Validation::new(Verdict::Malformed(Error::Decode(format!("Couldn't decode bech32: {}", err))))
+  };
+  if !bytes.first().map_or(false, |header| shelley::is_shelley_type(*header)) {
+    return Validation::new(Verdict::UnsupportedEra);
+  }
+  let decoded = match shelley::ShelleyAddress::from_bech32(&hrp, &bytes) {
+    Ok(decoded) => decoded,
+    Err(err) => return Validation::new(Verdict::Malformed(err))
+  };
+  let verdict = if decoded.network() != shelley::network_id(protocol_magic) {
+    Verdict::WrongNetwork
+  } else {
+    Verdict::Valid
+  };
+  Validation { verdict: verdict, protocol_magic: None, network_id: Some(decoded.network()) }
+}
+
+// Checks the Byron or Shelley address belongs to the network of `protocol_magic`
+pub fn validate_address(address: &str, protocol_magic: u32) -> Validation {
+  if is_bech32_era(address) {
+    validate_shelley(address, protocol_magic)
+  } else {
+    validate_byron(address, protocol_magic)
+  }
+}
+
+// Packed range of child keys. `xpubs` holds the keys back to back and
+// `addresses` the base58 addresses, each prefixed with its one byte length.
+pub struct DerivedRange {
+  xpubs: Vec<u8>,
+  addresses: Vec<u8>
+}
+
+impl DerivedRange {
+  fn with_capacity(count: u32) -> Self {
+    DerivedRange {
+      xpubs: Vec::with_capacity(count as usize * hdwallet::XPUB_SIZE),
+      addresses: Vec::with_capacity(count as usize * 128)
+    }
+  }
+
+  fn append(&mut self, other: DerivedRange) {
+    self.xpubs.extend_from_slice(&other.xpubs);
+    self.addresses.extend_from_slice(&other.addresses);
+  }
+
+  fn to_js<'a, C: Context<'a>>(&self, cx: &mut C) -> JsResult<'a, JsObject> {
+    let object = cx.empty_object();
+    for &(key, data) in [("xpubs", &self.xpubs), ("addresses", &self.addresses)].iter() {
+      let mut buffer = cx.buffer(data.len() as u32)?;
+      {
+        let guard = cx.lock();
+        buffer.borrow_mut(&guard).as_mut_slice::<u8>().copy_from_slice(data);
+      }
+      object.set(cx, key, buffer)?;
+    }
+    Ok(object)
+  }
+}
+
+fn derive_chunk(chain_xpub: &[u8], start: u32, count: u32, magic: NetworkMagic) -> Result<DerivedRange> {
+  let mut range = DerivedRange::with_capacity(count);
+  let mut xpub = [0 as u8; hdwallet::XPUB_SIZE];
+  for index in start..start + count {
+    if !wallet_wasm::wallet_derive_public(chain_xpub.as_ptr(), index, xpub.as_mut_ptr()) {
+      return Err(Error::Native(String::from("Can't derive public key")));
+    }
+    let address = byron_address(&xpub, magic)?;
+    range.xpubs.extend_from_slice(&xpub);
+    range.addresses.push(address.len() as u8);
+    range.addresses.extend_from_slice(address.as_bytes());
+  }
+  Ok(range)
+}
+
+fn derive_chunks_parallel(chain_xpub: &[u8], start: u32, count: u32, magic: NetworkMagic) -> Result<DerivedRange> {
+  let threads = cmp::min(num_cpus::get() as u32, (count + MIN_PARALLEL_RANGE - 1) / MIN_PARALLEL_RANGE);
+  if threads <= 1 {
+    return derive_chunk(chain_xpub, start, count, magic);
+  }
+  let chunk = (count + threads - 1) / threads;
+  let handles: Vec<_> = (0..threads).map(|thread| {
+    let chain_xpub = chain_xpub.to_vec();
+    let offset = cmp::min(thread * chunk, count);
+    let size = cmp::min(chunk, count - offset);
+    thread::spawn(move || derive_chunk(&chain_xpub, start + offset, size, magic))
+  }).collect();
+
+  let mut range = DerivedRange::with_capacity(count);
+  for handle in handles {
+    let chunk = handle.join().map_err(|_| Error::Native(String::from("Derivation thread panicked")))??;
+    range.append(chunk);
+  }
+  Ok(range)
+}
+
+pub struct RangeRequest {
+  account_xpub: [u8; hdwallet::XPUB_SIZE],
+  chain: u32,
+  start: u32,
+  count: u32,
+  magic: NetworkMagic,
+  parallel: bool
+}
+
+impl RangeRequest {
+  // Params: xpub: Buffer, chain: Number, start: Number, count: Number, protocolMagic: Number, parallel: Boolean
+  fn from_arguments(cx: &mut FunctionContext) -> NeonResult<Self> {
+    let xpub = cx.argument::<JsBuffer>(0)?;
+    let chain = cx.argument::<JsNumber>(1)?.value();
+    let start = cx.argument::<JsNumber>(2)?.value();
+    let count = cx.argument::<JsNumber>(3)?.value();
+    let protocol_magic = cx.argument::<JsNumber>(4)?.value();
+    let parallel = cx.argument::<JsBoolean>(5)?.value();
+
+    let account_xpub = {
+      let guard = cx.lock();
+      let xpub_buf = xpub.borrow(&guard);
+      let xpub_data = xpub_buf.as_slice::<u8>();
+      if xpub_data.len() != hdwallet::XPUB_SIZE {
+        Err(Error::InvalidLength { field: "XPub", actual: xpub_data.len(), expected: hdwallet::XPUB_SIZE })
+      } else {
+        let mut account_xpub = [0 as u8; hdwallet::XPUB_SIZE];
+        account_xpub.copy_from_slice(xpub_data);
+        Ok(account_xpub)
+      }
+    }.or_throw(cx)?;
+
+    RangeRequest::new(account_xpub, chain, start, count, protocol_magic, parallel).or_throw(cx)
+  }
+
+  fn new(
+    account_xpub: [u8; hdwallet::XPUB_SIZE], chain: f64, start: f64, count: f64, protocol_magic: f64, parallel: bool
+  ) -> Result<Self> {
+    let chain = u32_argument(chain, "chain")?;
+    if chain > 1 {
+      return Err(Error::InvalidArgument(format!("Wrong chain {} should be 0 (external) or 1 (internal)", chain)));
+    }
+    let start = u32_argument(start, "start")?;
+    let count = u32_argument(count, "count")?;
+    if count > MAX_RANGE_COUNT {
+      return Err(Error::InvalidArgument(format!("Wrong count {} should be at most {}", count, MAX_RANGE_COUNT)));
+    }
+    if start as u64 + count as u64 > HARDENED_INDEX as u64 {
+      return Err(Error::InvalidArgument(format!("Range {}..{} goes past the soft indices", start, start as u64 + count as u64)));
+    }
+    Ok(RangeRequest {
+      account_xpub: account_xpub, chain: chain, start: start, count: count,
+      magic: network_magic(u32_argument(protocol_magic, "protocol magic")?), parallel: parallel
+    })
+  }
+
+  fn derive(&self) -> Result<DerivedRange> {
+    let mut chain_xpub = [0 as u8; hdwallet::XPUB_SIZE];
+    derive_public_indices(self.account_xpub.as_ptr(), &[self.chain], chain_xpub.as_mut_ptr())?;
+    if self.parallel {
+      derive_chunks_parallel(&chain_xpub, self.start, self.count, self.magic)
+    } else {
+      derive_chunk(&chain_xpub, self.start, self.count, self.magic)
+    }
+  }
+}
+
+// Derives the child XPubs and Byron addresses of an account chain on a background thread
+pub struct RangeTask(RangeRequest);
+
+impl Task for RangeTask {
+  type Output = DerivedRange;
+  type Error = Error;
+  type JsEvent = JsObject;
+
+  fn perform(&self) -> Result<DerivedRange> {
+    handle_exception(|| self.0.derive())
+  }
+
+  fn complete(self, mut cx: TaskContext, result: Result<DerivedRange>) -> JsResult<JsObject> {
+    let range = result.or_throw(&mut cx)?;
+    range.to_js(&mut cx)
+  }
+}
+
+// Params: xpub: Buffer, chain: Number, start: Number, count: Number, protocolMagic: Number, parallel: Boolean
+pub fn derive_range(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let request = RangeRequest::from_arguments(&mut cx)?;
+
+  let range = handle_exception(|| request.derive()).or_throw(&mut cx)?;
+  range.to_js(&mut cx)
+}
+
+// Params: xpub: Buffer, chain: Number, start: Number, count: Number, protocolMagic: Number, parallel: Boolean,
+//         callback: Function
+pub fn derive_range_async(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let request = RangeRequest::from_arguments(&mut cx)?;
+  let callback = cx.argument::<JsFunction>(6)?;
+
+  RangeTask(request).schedule(callback);
+  Ok(cx.undefined())
+}
+
+// Params: address: String (base58)
+// Returns: { root: Buffer, type: String, protocolMagic: Number?, hasHdPayload: Boolean, crc: Number, crcValid: Boolean }
+pub fn decode(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let address = cx.argument::<JsString>(0)?.value();
+
+  let bytes = decode_base58(&address).or_throw(&mut cx)?;
+  let decoded = decode_byron(&bytes).or_throw(&mut cx)?;
+
+  let object = cx.empty_object();
+  let mut root = cx.buffer(decoded.root.len() as u32)?;
+  {
+    let guard = cx.lock();
+    root.borrow_mut(&guard).as_mut_slice::<u8>().copy_from_slice(&decoded.root);
+  }
+  object.set(&mut cx, "root", root)?;
+  let addr_type = cx.string(decoded.addr_type.name());
+  object.set(&mut cx, "type", addr_type)?;
+  if let Some(magic) = decoded.protocol_magic {
+    let magic = cx.number(magic as f64);
+    object.set(&mut cx, "protocolMagic", magic)?;
+  }
+  let has_hd_payload = cx.boolean(decoded.hd_payload.is_some());
+  object.set(&mut cx, "hasHdPayload", has_hd_payload)?;
+  let crc = cx.number(decoded.crc as f64);
+  object.set(&mut cx, "crc", crc)?;
+  let crc_valid = cx.boolean(decoded.crc_valid);
+  object.set(&mut cx, "crcValid", crc_valid)?;
+  Ok(object)
+}
+
+// Params: address: String, protocolMagic: Number
+// Returns: { verdict: String, protocolMagic: Number?, networkId: Number?, reason: String? }
+pub fn validate(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let address = cx.argument::<JsString>(0)?.value();
+  let protocol_magic = cx.argument::<JsNumber>(1)?.value();
+
+  let protocol_magic = u32_argument(protocol_magic, "protocol magic").or_throw(&mut cx)?;
+  let validation = validate_address(&address, protocol_magic);
+
+  let object = cx.empty_object();
+  let name = cx.string(validation.verdict.name());
+  object.set(&mut cx, "verdict", name)?;
+  if let Some(magic) = validation.protocol_magic {
+    let magic = cx.number(magic as f64);
+    object.set(&mut cx, "protocolMagic", magic)?;
+  }
+  if let Some(network) = validation.network_id {
+    let network = cx.number(network as f64);
+    object.set(&mut cx, "networkId", network)?;
+  }
+  if let Verdict::Malformed(ref err) = validation.verdict {
+    let reason = cx.string(err.to_string());
+    object.set(&mut cx, "reason", reason)?;
+  }
+  Ok(object)
+}
@@ -1,18 +1,77 @@
 use neon::borrow::*;
 use neon::types::BinaryData;
+use exception::*;
+use serde::{Deserialize, Deserializer};
+use std::cmp;
 use std::slice;
+use std::ptr;
+use std::ops::{Deref, DerefMut};
+use std::marker::PhantomData;
+use std::result;
+use std::sync::atomic::{compiler_fence, Ordering};
 
-pub struct MutBufferPtr {
+// Overwrites the data with zeros in a way the optimizer can't elide
+pub fn secure_zero(data: &mut [u8]) {
+  for byte in data.iter_mut() {
+    unsafe { ptr::write_volatile(byte, 0) };
+  }
+  compiler_fence(Ordering::SeqCst);
+}
+
+// Validates the size returned by a wallet_wasm function writing to a buffer of `max` bytes
+pub fn response_size(rsz: i32, max: usize) -> Result<usize> {
+  if rsz <= 0 { return Err(Error::Native(format!("Response {} <= 0", rsz))); }
+  if (rsz as usize) > max { return Err(Error::OutputTooLarge { size: rsz as usize, max: max }); }
+  Ok(rsz as usize)
+}
+
+// Bytes past the capacity of a guarded buffer. 0xff never occurs in UTF-8 text,
+// so a JSON response running past the capacity always changes them.
+const GUARD_SIZE: usize = 256;
+const GUARD_BYTE: u8 = 0xff;
+
+// Output buffer of the wallet_wasm functions, which write without bounds checks.
+// The capacities are proven upper bounds, the guard turns a wrong one into an error.
+pub struct GuardedBuffer {
+  data: SecureBuffer,
+  capacity: usize
+}
+
+impl GuardedBuffer {
+  pub fn new(capacity: usize) -> Self {
+    let mut data = SecureBuffer::new(capacity + GUARD_SIZE);
+    for byte in data[capacity..].iter_mut() {
+      *byte = GUARD_BYTE;
+    }
+    GuardedBuffer { data: data, capacity: capacity }
+  }
+
+  pub fn as_mut_ptr(&mut self) -> *mut u8 {
+    self.data.as_mut_ptr()
+  }
+
+  // The `rsz` bytes written by the function, once the guard is checked
+  pub fn response(&self, rsz: i32) -> Result<&[u8]> {
+    if self.data[self.capacity..].iter().any(|byte| *byte != GUARD_BYTE) {
+      return Err(Error::OutputTooLarge { size: cmp::max(rsz, 0) as usize, max: self.capacity });
+    }
+    response_size(rsz, self.capacity).map(|size| &self.data[..size])
+  }
+}
+
+// Raw pointer to a mutable buffer. Can't outlive the borrowed memory.
+pub struct MutBufferPtr<'a> {
   pub size: usize,
-  pub ptr: *mut u8
+  pub ptr: *mut u8,
+  _data: PhantomData<&'a [u8]>
 }
 
-impl MutBufferPtr {
-  pub fn from(slice: &mut [u8]) -> Self {
-    MutBufferPtr { ptr: slice.as_mut_ptr(), size: slice.len() }
+impl<'a> MutBufferPtr<'a> {
+  pub fn from(slice: &'a mut [u8]) -> Self {
+    MutBufferPtr { ptr: slice.as_mut_ptr(), size: slice.len(), _data: PhantomData }
   }
 
-  pub fn as_sized_slice<'a>(&'a self, size: usize) -> &'a mut [u8] {
+  pub fn as_sized_slice(&self, size: usize) -> &'a mut [u8] {
     if size > self.size { panic!("Size {} >= {}", size, self.size) }
     unsafe {
       slice::from_raw_parts_mut(self.ptr, size)
@@ -20,19 +79,107 @@ impl MutBufferPtr {
   }
 }
 
-pub struct BufferPtr {
+// Raw pointer to a read only buffer. Can't outlive the borrowed memory.
+pub struct BufferPtr<'a> {
   pub size: usize,
-  pub ptr: *const u8
+  pub ptr: *const u8,
+  _data: PhantomData<&'a [u8]>
 }
 
-impl<'a> From<RefMut<'a, BinaryData<'a>>> for MutBufferPtr {
+impl<'a> From<RefMut<'a, BinaryData<'a>>> for MutBufferPtr<'a> {
   fn from(reference: RefMut<'a, BinaryData<'a>>) -> Self {
-    MutBufferPtr { size: reference.len(), ptr: reference.as_mut_slice::<u8>().as_mut_ptr() }
+    MutBufferPtr {
+      size: reference.len(), ptr: reference.as_mut_slice::<u8>().as_mut_ptr(), _data: PhantomData
+    }
   }
 }
 
-impl<'a> From<Ref<'a, BinaryData<'a>>> for BufferPtr {
+impl<'a> From<Ref<'a, BinaryData<'a>>> for BufferPtr<'a> {
   fn from(reference: Ref<'a, BinaryData<'a>>) -> Self {
-    BufferPtr { size: reference.len(), ptr: reference.as_slice::<u8>().as_ptr() }
+    BufferPtr { size: reference.len(), ptr: reference.as_slice::<u8>().as_ptr(), _data: PhantomData }
+  }
+}
+
+// Fixed size native scratch buffer, wiped on drop.
+// Used for every output which can contain key material.
+pub struct SecureBuffer(Vec<u8>);
+
+impl SecureBuffer {
+  pub fn new(size: usize) -> Self {
+    SecureBuffer(vec![0; size])
+  }
+}
+
+impl Deref for SecureBuffer {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    &self.0
+  }
+}
+
+impl DerefMut for SecureBuffer {
+  fn deref_mut(&mut self) -> &mut [u8] {
+    &mut self.0
+  }
+}
+
+impl Drop for SecureBuffer {
+  fn drop(&mut self) {
+    secure_zero(&mut self.0);
+  }
+}
+
+// Native copy of a JS string argument (mnemonics, JSON with keys), wiped on drop.
+pub struct SecureString(String);
+
+impl From<String> for SecureString {
+  fn from(string: String) -> Self {
+    SecureString(string)
   }
-}
\ No newline at end of file
+}
+
+impl Deref for SecureString {
+  type Target = str;
+
+  fn deref(&self) -> &str {
+    &self.0
+  }
+}
+
+impl Drop for SecureString {
+  fn drop(&mut self) {
+    secure_zero(unsafe { self.0.as_bytes_mut() });
+  }
+}
+
+// Lets request structs hold key material parsed from JSON
+impl<'de> Deserialize<'de> for SecureString {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> result::Result<Self, D::Error> {
+    String::deserialize(deserializer).map(SecureString)
+  }
+}
+
+// Decodes a hex string holding key material
+pub fn decode_hex_secure(string: &str, field: &'static str) -> Result<SecureBuffer> {
+  fn nibble(char: u8) -> Option<u8> {
+    match char {
+      b'0'..=b'9' => Some(char - b'0'),
+      b'a'..=b'f' => Some(char - b'a' + 10),
+      b'A'..=b'F' => Some(char - b'A' + 10),
+      _ => None
+    }
+  }
+  let chars = string.as_bytes();
+  if chars.len() % 2 != 0 {
+    return Err(Error::Decode(format!("Odd {} hex length {}", field, chars.len())));
+  }
+  let mut output = SecureBuffer::new(chars.len() / 2);
+  for (index, pair) in chars.chunks(2).enumerate() {
+    match (nibble(pair[0]), nibble(pair[1])) {
+      (Some(high), Some(low)) => output[index] = high << 4 | low,
+      _ => return Err(Error::Decode(format!("Invalid {} hex at {}", field, index * 2)))
+    }
+  }
+  Ok(output)
+}
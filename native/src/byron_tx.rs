@@ -0,0 +1,568 @@
+use neon::prelude::*;
+use exception::*;
+use json::*;
+use buffer::*;
+use params::ProtocolParams;
+use address::{decode_base58, decode_byron, is_bech32_era};
+use hdwallet::HARDENED_INDEX;
+use shelley_tx::{
+  decode_fixed_hex, parse_coin, set_bytes, set_coin, sum_coins, tx_hash, Addressing, OutputRequest, TxPointer,
+  MAX_FEE_ROUNDS, TX_ID_SIZE
+};
+use transaction::{byron_signed_data, SIGN_TX};
+use selection::{self, Balanced, SelectionPolicy, Utxo};
+use task::{RequestTask, ToJs};
+use cbor;
+use cardano::hdwallet::{self, DerivationScheme, Signature, XPrv};
+use cardano::util::hex;
+use std::cmp;
+
+// Inputs of `wallet_spend`, the addressing is only needed to sign
+#[derive(Deserialize)]
+pub struct ByronInput {
+  pub ptr: TxPointer,
+  pub value: OutputRequest,
+  #[serde(default)]
+  pub addressing: Option<Addressing>,
+  // Creation slot, used by the OldestFirst policy
+  #[serde(default)]
+  pub slot: Option<u64>
+}
+
+// Inputs of `wallet_move`, addressed by the hardened `[account, index]` of random addresses
+#[derive(Deserialize)]
+struct MoveInput {
+  ptr: TxPointer,
+  value: String,
+  addressing: [u32; 2]
+}
+
+#[derive(Deserialize)]
+pub struct WalletConfig {
+  pub protocol_magic: u32
+}
+
+#[derive(Deserialize)]
+pub struct ByronWallet {
+  pub root_cached_key: SecureString,
+  pub derivation_scheme: DerivationScheme,
+  #[serde(default)]
+  pub selection_policy: SelectionPolicy,
+  pub config: WalletConfig
+}
+
+#[derive(Deserialize)]
+struct EstimateRequest {
+  inputs: Vec<ByronInput>,
+  outputs: Vec<OutputRequest>,
+  change_addr: String,
+  #[serde(default)]
+  protocol_params: Option<ProtocolParams>,
+  #[serde(default)]
+  selection_policy: SelectionPolicy,
+  #[serde(default)]
+  selection_seed: Option<u64>
+}
+
+#[derive(Deserialize)]
+struct SpendRequest {
+  wallet: ByronWallet,
+  inputs: Vec<ByronInput>,
+  outputs: Vec<OutputRequest>,
+  change_addr: String,
+  #[serde(default)]
+  protocol_params: Option<ProtocolParams>,
+  #[serde(default)]
+  selection_seed: Option<u64>
+}
+
+#[derive(Deserialize)]
+struct DrainRequest {
+  wallet: ByronWallet,
+  inputs: Vec<ByronInput>,
+  output: String,
+  #[serde(default)]
+  protocol_params: Option<ProtocolParams>
+}
+
+#[derive(Deserialize)]
+struct MoveRequest {
+  wallet: ByronWallet,
+  inputs: Vec<MoveInput>,
+  output: String,
+  #[serde(default)]
+  protocol_params: Option<ProtocolParams>
+}
+
+// Byron outputs are base58 addresses, kept in their CBOR form
+pub fn byron_address(address: &str) -> Result<Vec<u8>> {
+  if is_bech32_era(address) {
+    return Err(Error::InvalidArgument(format!("{} is not a Byron address", address)));
+  }
+  let bytes = decode_base58(address)?;
+  decode_byron(&bytes)?;
+  Ok(bytes)
+}
+
+pub fn tx_pointer(ptr: &TxPointer) -> Result<(Vec<u8>, u32)> {
+  Ok((decode_fixed_hex(&ptr.id, "transaction id", TX_ID_SIZE)?, ptr.index))
+}
+
+// `[inputs, outputs, attributes]`, hashed into the transaction id
+pub fn encode_tx(inputs: &[(Vec<u8>, u32)], outputs: &[(Vec<u8>, u64)]) -> Vec<u8> {
+  let mut writer = cbor::Writer::new();
+  writer.array(3).indefinite_array();
+  for &(ref id, index) in inputs.iter() {
+    let mut pointer = cbor::Writer::new();
+    pointer.array(2).bytes(id).unsigned(index as u64);
+    writer.array(2).unsigned(0).tag(cbor::TAG_ENCODED_CBOR).bytes(&pointer.into_bytes());
+  }
+  writer.end_indefinite().indefinite_array();
+  for &(ref address, value) in outputs.iter() {
+    writer.array(2).raw(address).unsigned(value);
+  }
+  writer.end_indefinite().map(0);
+  writer.into_bytes()
+}
+
+// `[tx, witnesses]` with one public key witness per `(xpub, signature)` pair
+pub fn encode_signed(tx: &[u8], witnesses: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+  let mut writer = cbor::Writer::new();
+  writer.array(2).raw(tx).array(witnesses.len());
+  for &(ref xpub, ref signature) in witnesses.iter() {
+    let mut witness = cbor::Writer::new();
+    witness.array(2).bytes(xpub).bytes(signature);
+    writer.array(2).unsigned(0).tag(cbor::TAG_ENCODED_CBOR).bytes(&witness.into_bytes());
+  }
+  writer.into_bytes()
+}
+
+// Size of the signed transaction, placeholder witnesses have the size of real ones
+pub fn signed_size(inputs: &[(Vec<u8>, u32)], outputs: &[(Vec<u8>, u64)]) -> usize {
+  let placeholder = (vec![0; hdwallet::XPUB_SIZE], vec![0; hdwallet::SIGNATURE_SIZE]);
+  let witnesses: Vec<(Vec<u8>, Vec<u8>)> = inputs.iter().map(|_| placeholder.clone()).collect();
+  encode_signed(&encode_tx(inputs, outputs), &witnesses).len()
+}
+
+fn check_size(size: usize, params: &ProtocolParams) -> Result<()> {
+  if size > params.max_tx_size {
+    return Err(Error::TransactionTooLarge { size: size, max: params.max_tx_size });
+  }
+  Ok(())
+}
+
+struct Balance {
+  outputs: Vec<(Vec<u8>, u64)>,
+  fee: u64,
+  size: usize,
+  change_used: bool
+}
+
+impl Balanced for Balance {
+  fn change_used(&self) -> bool {
+    self.change_used
+  }
+}
+
+pub struct Estimate {
+  // Positions of the selected inputs, in the order they are spent
+  pub selected: Vec<usize>,
+  pub inputs: Vec<(Vec<u8>, u32)>,
+  // Requested outputs followed by the change, if any
+  pub outputs: Vec<(Vec<u8>, u64)>,
+  pub fee: u64,
+  pub size: usize,
+  pub change_used: bool
+}
+
+// Pays the outputs and the fee from `available`, adding a change output when
+// the leftover covers the cost of that output, leaving it to the fee otherwise.
+// The cost of the change output is the fee of its bytes plus the minimum UTxO
+// value it has to hold.
+fn balance(
+  inputs: &[(Vec<u8>, u32)], outputs: &[(Vec<u8>, u64)], change_address: &[u8], available: u64,
+  params: &ProtocolParams
+) -> Result<Balance> {
+  let spent = sum_coins(outputs.iter().map(|&(_, value)| value))?;
+  let size = signed_size(inputs, outputs);
+  check_size(size, params)?;
+  let min_fee = params.min_fee(size);
+  let needed = spent.saturating_add(min_fee);
+  let left = available.checked_sub(needed)
+    .ok_or(Error::InsufficientFunds { available: available, needed: needed })?;
+
+  // The change never holds more than `left`, so this size bounds the final one
+  let mut with_change = outputs.to_vec();
+  with_change.push((change_address.to_vec(), left));
+  let change_size = signed_size(inputs, &with_change);
+  let change_cost = params.min_fee(change_size).saturating_sub(min_fee).saturating_add(cmp::max(params.min_utxo, 1));
+  if left < change_cost {
+    return Ok(Balance { outputs: outputs.to_vec(), fee: available - spent, size: size, change_used: false });
+  }
+  check_size(change_size, params)?;
+
+  let mut fee = params.min_fee(change_size);
+  for _ in 0..MAX_FEE_ROUNDS {
+    let needed = spent.saturating_add(fee).saturating_add(cmp::max(params.min_utxo, 1));
+    if available < needed {
+      return Err(Error::InsufficientFunds { available: available, needed: needed });
+    }
+    let change = available - spent - fee;
+    with_change.truncate(outputs.len());
+    with_change.push((change_address.to_vec(), change));
+    let size = signed_size(inputs, &with_change);
+    check_size(size, params)?;
+    let required = params.min_fee(size);
+    if required <= fee {
+      return Ok(Balance { outputs: with_change, fee: fee, size: size, change_used: true });
+    }
+    fee = required;
+  }
+  Err(Error::Native(String::from("Fee calculation didn't converge")))
+}
+
+// Selects the inputs paying for the outputs and the fee of the transaction spending them
+pub fn estimate(
+  inputs: &[ByronInput], outputs: &[OutputRequest], change_addr: &str, params: &ProtocolParams,
+  policy: SelectionPolicy, seed: Option<u64>
+) -> Result<Estimate> {
+  if inputs.is_empty() {
+    return Err(Error::InvalidArgument(String::from("Transaction has no inputs")));
+  }
+  let mut pointers = Vec::with_capacity(inputs.len());
+  let mut values = Vec::with_capacity(inputs.len());
+  for input in inputs.iter() {
+    pointers.push(tx_pointer(&input.ptr)?);
+    values.push(parse_coin(&input.value.value)?);
+  }
+  let mut encoded_outputs = Vec::with_capacity(outputs.len());
+  for output in outputs.iter() {
+    let value = parse_coin(&output.value)?;
+    if value < params.min_utxo {
+      return Err(Error::InvalidArgument(format!("Output of {} is below the minimum UTxO value {}", value, params.min_utxo)));
+    }
+    encoded_outputs.push((byron_address(&output.address)?, value));
+  }
+  let change_address = byron_address(change_addr)?;
+
+  let utxos: Vec<Utxo> = values.iter().zip(inputs.iter())
+    .map(|(value, input)| Utxo { value: *value, slot: input.slot })
+    .collect();
+  let targets: Vec<u64> = encoded_outputs.iter().map(|&(_, value)| value).collect();
+  let mut rng = selection::rng(seed)?;
+  let (selected, balanced) = selection::select(policy, &utxos, &targets, &mut rng, |selected| {
+    let selected_inputs: Vec<(Vec<u8>, u32)> = selected.iter().map(|position| pointers[*position].clone()).collect();
+    let available = sum_coins(selected.iter().map(|position| values[*position]))?;
+    balance(&selected_inputs, &encoded_outputs, &change_address, available, params)
+  })?;
+  Ok(Estimate {
+    inputs: selected.iter().map(|position| pointers[*position].clone()).collect(), selected: selected,
+    outputs: balanced.outputs, fee: balanced.fee, size: balanced.size, change_used: balanced.change_used
+  })
+}
+
+// Sends everything in `available` to `address`, the fee being taken from that output.
+// Returns the output value, the fee and the signed size.
+pub fn drain(
+  inputs: &[(Vec<u8>, u32)], address: &[u8], available: u64, params: &ProtocolParams
+) -> Result<(u64, u64, usize)> {
+  let mut fee = params.fee_constant;
+  for _ in 0..MAX_FEE_ROUNDS {
+    let needed = fee.saturating_add(cmp::max(params.min_utxo, 1));
+    if available < needed {
+      return Err(Error::InsufficientFunds { available: available, needed: needed });
+    }
+    let value = available - fee;
+    let size = signed_size(inputs, &[(address.to_vec(), value)]);
+    check_size(size, params)?;
+    let required = params.min_fee(size);
+    if required <= fee {
+      return Ok((value, fee, size));
+    }
+    fee = required;
+  }
+  Err(Error::Native(String::from("Fee calculation didn't converge")))
+}
+
+// BIP44 paths account'/change/index, under the wallet root, of the inputs at `positions`
+fn spend_paths(inputs: &[ByronInput], positions: &[usize]) -> Result<Vec<Vec<u32>>> {
+  let mut paths = Vec::with_capacity(positions.len());
+  for position in positions.iter() {
+    let addressing = inputs[*position].addressing.as_ref()
+      .ok_or_else(|| Error::InvalidArgument(format!("Input {} has no addressing", position)))?;
+    if addressing.account >= HARDENED_INDEX {
+      return Err(Error::InvalidArgument(format!("Wrong account {} should be below {}", addressing.account, HARDENED_INDEX)));
+    }
+    paths.push(vec![HARDENED_INDEX | addressing.account, addressing.change, addressing.index]);
+  }
+  Ok(paths)
+}
+
+// Keys at `paths` under the wallet root, with the derivation scheme of the wallet
+pub fn derive_keys(wallet: &ByronWallet, paths: &[Vec<u32>]) -> Result<Vec<XPrv>> {
+  let xprv = decode_hex_secure(&wallet.root_cached_key, "root key")?;
+  let root = XPrv::from_slice_verified(&xprv)
+    .map_err(|err| Error::InvalidArgument(format!("Invalid root key: {:?}", err)))?;
+  Ok(paths.iter().map(|path| {
+    path.iter().fold(root.clone(), |key, index| key.derive(wallet.derivation_scheme, *index))
+  }).collect())
+}
+
+// Witnesses `tx` with the keys at `paths` under the wallet root
+fn sign_tx(wallet: &ByronWallet, tx: &[u8], paths: &[Vec<u32>]) -> Result<Vec<u8>> {
+  let data = byron_signed_data(SIGN_TX, wallet.config.protocol_magic, &tx_hash(tx));
+  let witnesses = derive_keys(wallet, paths)?.iter().map(|key| {
+    let signature: Signature<()> = key.sign(&data);
+    (key.public().as_ref().to_vec(), signature.as_ref().to_vec())
+  }).collect::<Vec<_>>();
+  Ok(encode_signed(tx, &witnesses))
+}
+
+fn transaction_obj<'a, C: Context<'a>>(
+  cx: &mut C, tx: &[u8], tx_id: &[u8], fee: u64, change_used: bool
+) -> JsResult<'a, JsObject> {
+  let object = cx.empty_object();
+  set_bytes(cx, object, "cbor_encoded_tx", tx)?;
+  let tx_id = cx.string(hex::encode(tx_id));
+  object.set(cx, "tx_id", tx_id)?;
+  set_coin(cx, object, "fee", fee)?;
+  let change_used = cx.boolean(change_used);
+  object.set(cx, "change_used", change_used)?;
+  Ok(object)
+}
+
+// Sets `key` to the `{ id, index }` pointers of `inputs`
+pub fn set_pointers<'a, C: Context<'a>>(
+  cx: &mut C, object: Handle<'a, JsObject>, key: &str, inputs: &[(String, u32)]
+) -> NeonResult<()> {
+  let array = JsArray::new(cx, inputs.len() as u32);
+  for (position, &(ref id, index)) in inputs.iter().enumerate() {
+    let pointer = cx.empty_object();
+    let id = cx.string(id);
+    pointer.set(cx, "id", id)?;
+    let index = cx.number(index as f64);
+    pointer.set(cx, "index", index)?;
+    array.set(cx, position as u32, pointer)?;
+  }
+  object.set(cx, key, array).map(|_| ())
+}
+
+// Signed transaction with the summary given back to JS
+struct SignedTx {
+  tx: Vec<u8>,
+  tx_id: [u8; TX_ID_SIZE],
+  fee: u64,
+  change_used: bool,
+  // Pointers of the inputs picked by the coin selection
+  selected: Option<Vec<(String, u32)>>,
+  // Amount received by the output of send all transactions
+  value: Option<u64>
+}
+
+impl ToJs for SignedTx {
+  fn to_js<'a, C: Context<'a>>(&self, cx: &mut C) -> JsResult<'a, JsObject> {
+    let object = transaction_obj(cx, &self.tx, &self.tx_id, self.fee, self.change_used)?;
+    if let Some(ref selected) = self.selected {
+      set_pointers(cx, object, "selected_inputs", selected)?;
+    }
+    if let Some(value) = self.value {
+      set_coin(cx, object, "value", value)?;
+    }
+    Ok(object)
+  }
+}
+
+fn selected_pointers(inputs: &[ByronInput], selected: &[usize]) -> Vec<(String, u32)> {
+  selected.iter().map(|position| (inputs[*position].ptr.id.clone(), inputs[*position].ptr.index)).collect()
+}
+
+// Fee of the Byron transaction `spend` would build, without any key.
+// Params: params: JSONString { inputs, outputs, change_addr, protocol_params?, selection_policy?, selection_seed? }
+// Returns: { fee: String, tx_size: Number, change_used: Boolean, selected_inputs: Array<{ id, index }> }
+fn estimate_request(params: &str) -> Result<(Estimate, Vec<(String, u32)>)> {
+  let request = parse_request::<EstimateRequest>(params)?;
+  let params = request.protocol_params.unwrap_or_else(ProtocolParams::byron);
+  let estimate = estimate(
+    &request.inputs, &request.outputs, &request.change_addr, &params,
+    request.selection_policy, request.selection_seed
+  )?;
+  let selected = selected_pointers(&request.inputs, &estimate.selected);
+  Ok((estimate, selected))
+}
+
+pub fn estimate_fee(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let params = cx.argument::<JsString>(0)?.value();
+
+  let (estimate, selected) = handle_exception(|| estimate_request(&params)).or_throw(&mut cx)?;
+
+  let object = cx.empty_object();
+  set_coin(&mut cx, object, "fee", estimate.fee)?;
+  let tx_size = cx.number(estimate.size as f64);
+  object.set(&mut cx, "tx_size", tx_size)?;
+  let change_used = cx.boolean(estimate.change_used);
+  object.set(&mut cx, "change_used", change_used)?;
+  set_pointers(&mut cx, object, "selected_inputs", &selected)?;
+  Ok(object)
+}
+
+fn spend_tx(params: &str) -> Result<SignedTx> {
+  let request = parse_request::<SpendRequest>(params)?;
+  let params = request.protocol_params.unwrap_or_else(ProtocolParams::byron);
+  let estimate = estimate(
+    &request.inputs, &request.outputs, &request.change_addr, &params,
+    request.wallet.selection_policy, request.selection_seed
+  )?;
+  let paths = spend_paths(&request.inputs, &estimate.selected)?;
+  let body = encode_tx(&estimate.inputs, &estimate.outputs);
+  Ok(SignedTx {
+    tx: sign_tx(&request.wallet, &body, &paths)?, tx_id: tx_hash(&body), fee: estimate.fee,
+    change_used: estimate.change_used, selected: Some(selected_pointers(&request.inputs, &estimate.selected)),
+    value: None
+  })
+}
+
+// Builds every Byron spend, with the coin selection policy of the wallet and the Byron
+// mainnet fee policy unless protocol parameters are given. Keys are derived from the
+// wallet root along account'/change/index.
+// Params: params: JSONString { wallet, inputs, outputs, change_addr, protocol_params?, selection_seed? }
+// Returns: { cbor_encoded_tx: Buffer, tx_id: String, fee: String, change_used: Boolean,
+//            selected_inputs: Array<{ id, index }> }
+pub fn spend(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+
+  let tx = handle_exception(|| spend_tx(&params)).or_throw(&mut cx)?;
+  tx.to_js(&mut cx)
+}
+
+// Params: params: JSONString, callback: Function
+pub fn spend_async(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let callback = cx.argument::<JsFunction>(1)?;
+
+  RequestTask::new(spend_tx, params).schedule(callback);
+  Ok(cx.undefined())
+}
+
+fn drain_tx(params: &str) -> Result<SignedTx> {
+  let request = parse_request::<DrainRequest>(params)?;
+  if request.inputs.is_empty() {
+    return Err(Error::InvalidArgument(String::from("Transaction has no inputs")));
+  }
+  let params = request.protocol_params.unwrap_or_else(ProtocolParams::byron);
+  let inputs = request.inputs.iter().map(|input| tx_pointer(&input.ptr)).collect::<Result<Vec<_>>>()?;
+  let values = request.inputs.iter().map(|input| parse_coin(&input.value.value)).collect::<Result<Vec<_>>>()?;
+  let address = byron_address(&request.output)?;
+  let (value, fee, _) = drain(&inputs, &address, sum_coins(values.into_iter())?, &params)?;
+  let paths = spend_paths(&request.inputs, &(0..inputs.len()).collect::<Vec<_>>())?;
+  let body = encode_tx(&inputs, &[(address, value)]);
+  Ok(SignedTx {
+    tx: sign_tx(&request.wallet, &body, &paths)?, tx_id: tx_hash(&body), fee: fee, change_used: false, selected: None,
+    value: Some(value)
+  })
+}
+
+// Send all for BIP44 wallets: every input goes to `output`, which pays the fee.
+// Params: params: JSONString { wallet, inputs, output, protocol_params? }
+// Returns: { cbor_encoded_tx: Buffer, tx_id: String, fee: String, change_used: false, value: String }
+pub fn drain_func(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+
+  let tx = handle_exception(|| drain_tx(&params)).or_throw(&mut cx)?;
+  tx.to_js(&mut cx)
+}
+
+// Params: params: JSONString, callback: Function
+pub fn drain_async(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let callback = cx.argument::<JsFunction>(1)?;
+
+  RequestTask::new(drain_tx, params).schedule(callback);
+  Ok(cx.undefined())
+}
+
+fn move_tx(params: &str) -> Result<SignedTx> {
+  let request = parse_request::<MoveRequest>(params)?;
+  if request.inputs.is_empty() {
+    return Err(Error::InvalidArgument(String::from("Transaction has no inputs")));
+  }
+  let params = request.protocol_params.unwrap_or_else(ProtocolParams::byron);
+  let inputs = request.inputs.iter().map(|input| tx_pointer(&input.ptr)).collect::<Result<Vec<_>>>()?;
+  let values = request.inputs.iter().map(|input| parse_coin(&input.value)).collect::<Result<Vec<_>>>()?;
+  let address = byron_address(&request.output)?;
+  let (value, fee, _) = drain(&inputs, &address, sum_coins(values.into_iter())?, &params)?;
+  let paths = request.inputs.iter().map(|input| input.addressing.to_vec()).collect::<Vec<_>>();
+  let body = encode_tx(&inputs, &[(address, value)]);
+  Ok(SignedTx {
+    tx: sign_tx(&request.wallet, &body, &paths)?, tx_id: tx_hash(&body), fee: fee, change_used: false, selected: None,
+    value: None
+  })
+}
+
+// `wallet_move` with caller supplied protocol parameters: every input goes to
+// `output`, which pays the fee.
+// Params: params: JSONString { wallet, inputs, output, protocol_params? }
+// Returns: { cbor_encoded_tx: Buffer, tx_id: String, fee: String, change_used: Boolean }
+pub fn move_func(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+
+  let tx = handle_exception(|| move_tx(&params)).or_throw(&mut cx)?;
+  tx.to_js(&mut cx)
+}
+
+// Params: params: JSONString, callback: Function
+pub fn move_async(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let callback = cx.argument::<JsFunction>(1)?;
+
+  RequestTask::new(move_tx, params).schedule(callback);
+  Ok(cx.undefined())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use address::{byron_address as encode_address, network_magic};
+  use wallet_wasm;
+
+  const SEED: [u8; hdwallet::SEED_SIZE] = [7; hdwallet::SEED_SIZE];
+  const MAINNET: u32 = 764824073;
+
+  fn root_key() -> XPrv {
+    let mut xprv = [0 as u8; hdwallet::XPRV_SIZE];
+    wallet_wasm::wallet_from_seed(SEED.as_ptr(), xprv.as_mut_ptr());
+    XPrv::from_slice_verified(&xprv).unwrap()
+  }
+
+  fn address(root: &XPrv, change: u32, index: u32) -> String {
+    // The wallet root is the m/44'/1815' key, spends derive account'/change/index from it
+    let key = [HARDENED_INDEX, change, index].iter()
+      .fold(root.clone(), |key, index| key.derive(DerivationScheme::V2, *index));
+    encode_address(key.public().as_ref(), network_magic(MAINNET)).unwrap()
+  }
+
+  #[test]
+  fn estimated_fee_is_the_fee_of_the_default_spend() {
+    let root = root_key();
+    let inputs: Vec<String> = (0..5).map(|index| format!(
+      "{{\"ptr\":{{\"id\":\"{:064x}\",\"index\":{}}},\"value\":{{\"address\":\"{}\",\"value\":\"{}\"}},\
+       \"addressing\":{{\"account\":0,\"change\":0,\"index\":{}}}}}",
+      index, index, address(&root, 0, index), (index + 1) * 1_000_000, index
+    )).collect();
+    let outputs = format!("[{{\"address\":\"{}\",\"value\":\"7000000\"}}]", address(&root, 0, 100));
+    let change_addr = address(&root, 1, 0);
+    let wallet = format!(
+      "{{\"root_cached_key\":\"{}\",\"derivation_scheme\":\"V2\",\"config\":{{\"protocol_magic\":{}}}}}",
+      hex::encode(root.as_ref()), MAINNET
+    );
+
+    let (estimate, selected) = estimate_request(&format!(
+      "{{\"inputs\":[{}],\"outputs\":{},\"change_addr\":\"{}\"}}", inputs.join(","), outputs, change_addr
+    )).unwrap();
+    let tx = spend_tx(&format!(
+      "{{\"wallet\":{},\"inputs\":[{}],\"outputs\":{},\"change_addr\":\"{}\"}}", wallet, inputs.join(","), outputs, change_addr
+    )).unwrap();
+    assert_eq!(estimate.fee, tx.fee);
+    assert_eq!(estimate.change_used, tx.change_used);
+    assert_eq!(Some(selected), tx.selected);
+  }
+}
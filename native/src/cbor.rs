@@ -0,0 +1,235 @@
+use std::io::{BufRead, Cursor};
+use cbor_event::{self, Len, Type};
+use cbor_event::de::Deserializer;
+use exception::*;
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+
+// Tag of CBOR data embedded in a byte string
+pub const TAG_ENCODED_CBOR: u64 = 24;
+
+const BREAK: u8 = 0xff;
+
+// Nesting allowed by `skip`, far above the one of the structures we read,
+// so that hostile input can't exhaust the stack
+pub const MAX_DEPTH: usize = 64;
+
+// cbor_event deserializer over a borrowed slice. The cursor offset locates errors
+// and delimits the raw items we keep (hashed bodies, address attributes).
+pub type Reader<'a> = Deserializer<Cursor<&'a [u8]>>;
+
+impl From<cbor_event::Error> for Error {
+  fn from(err: cbor_event::Error) -> Self {
+    Error::Decode(format!("Invalid CBOR: {:?}", err))
+  }
+}
+
+pub fn reader(data: &[u8]) -> Reader {
+  Deserializer::from(Cursor::new(data))
+}
+
+fn data<'a>(reader: &Reader<'a>) -> &'a [u8] {
+  *reader.as_ref().get_ref()
+}
+
+pub fn position(reader: &Reader) -> usize {
+  reader.as_ref().position() as usize
+}
+
+pub fn error(reader: &Reader, msg: &str) -> Error {
+  Error::Decode(format!("Invalid CBOR at offset {}: {}", position(reader), msg))
+}
+
+fn is_break(reader: &Reader) -> bool {
+  data(reader).get(position(reader)) == Some(&BREAK)
+}
+
+// Steps into a definite or indefinite container: true while items are left.
+// cbor_event leaves the break of indefinite containers to the caller.
+pub fn has_next(reader: &mut Reader, length: Len, index: u64) -> Result<bool> {
+  match length {
+    Len::Len(length) => Ok(index < length),
+    Len::Indefinite => if is_break(reader) {
+      reader.as_mut_ref().consume(1);
+      Ok(false)
+    } else {
+      Ok(true)
+    }
+  }
+}
+
+// Bytes of a `#6.24(bytes .cbor T)` item
+pub fn encoded_cbor(reader: &mut Reader) -> Result<Vec<u8>> {
+  if reader.tag()? != TAG_ENCODED_CBOR {
+    return Err(error(reader, "expected encoded CBOR tag 24"));
+  }
+  Ok(reader.bytes()?)
+}
+
+// cbor_event::Value would skip any item too, but recurses without bound
+pub fn skip(reader: &mut Reader) -> Result<()> {
+  skip_nested(reader, 0)
+}
+
+fn skip_nested(reader: &mut Reader, depth: usize) -> Result<()> {
+  if depth > MAX_DEPTH {
+    return Err(error(reader, &format!("nesting deeper than {}", MAX_DEPTH)));
+  }
+  match reader.cbor_type()? {
+    Type::UnsignedInteger => { reader.unsigned_integer()?; },
+    Type::NegativeInteger => { reader.negative_integer()?; },
+    Type::Bytes => { reader.bytes()?; },
+    Type::Text => { reader.text()?; },
+    Type::Array => {
+      let length = reader.array()?;
+      let mut index = 0;
+      while has_next(reader, length, index)? {
+        skip_nested(reader, depth + 1)?;
+        index += 1;
+      }
+    },
+    Type::Map => {
+      let length = reader.map()?;
+      let mut index = 0;
+      while has_next(reader, length, index)? {
+        skip_nested(reader, depth + 1)?;
+        skip_nested(reader, depth + 1)?;
+        index += 1;
+      }
+    },
+    Type::Tag => {
+      reader.tag()?;
+      skip_nested(reader, depth + 1)?;
+    },
+    Type::Special => {
+      if is_break(reader) {
+        return Err(error(reader, "unexpected break"));
+      }
+      reader.special()?;
+    }
+  }
+  Ok(())
+}
+
+// Encoded bytes of the next item
+pub fn raw<'a>(reader: &mut Reader<'a>) -> Result<&'a [u8]> {
+  let start = position(reader);
+  skip(reader)?;
+  Ok(&data(reader)[start..position(reader)])
+}
+
+pub fn finish(reader: &Reader) -> Result<()> {
+  if position(reader) < data(reader).len() {
+    return Err(error(reader, "trailing bytes"));
+  }
+  Ok(())
+}
+
+// CRC32 (IEEE) protecting Byron addresses
+pub fn crc32(data: &[u8]) -> u32 {
+  let mut crc = !0u32;
+  for byte in data {
+    crc ^= *byte as u32;
+    for _ in 0..8 {
+      crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+    }
+  }
+  !crc
+}
+
+// CBOR encoder using the shortest argument encodings
+#[derive(Default)]
+pub struct Writer {
+  data: Vec<u8>
+}
+
+impl Writer {
+  pub fn new() -> Self {
+    Writer { data: Vec::new() }
+  }
+
+  pub fn len(&self) -> usize {
+    self.data.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+
+  fn header(&mut self, major: u8, argument: u64) -> &mut Self {
+    let major = major << 5;
+    if argument < 24 {
+      self.data.push(major | argument as u8);
+    } else if argument <= 0xff {
+      self.data.push(major | 24);
+      self.data.push(argument as u8);
+    } else if argument <= 0xffff {
+      self.data.push(major | 25);
+      self.data.extend_from_slice(&[(argument >> 8) as u8, argument as u8]);
+    } else if argument <= 0xffff_ffff {
+      self.data.push(major | 26);
+      for shift in [24, 16, 8, 0].iter() { self.data.push((argument >> shift) as u8); }
+    } else {
+      self.data.push(major | 27);
+      for shift in [56, 48, 40, 32, 24, 16, 8, 0].iter() { self.data.push((argument >> shift) as u8); }
+    }
+    self
+  }
+
+  pub fn unsigned(&mut self, value: u64) -> &mut Self {
+    self.header(MAJOR_UNSIGNED, value)
+  }
+
+  pub fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+    self.header(MAJOR_BYTES, bytes.len() as u64);
+    self.data.extend_from_slice(bytes);
+    self
+  }
+
+  pub fn array(&mut self, length: usize) -> &mut Self {
+    self.header(MAJOR_ARRAY, length as u64)
+  }
+
+  // Items follow until `end_indefinite`, as in Byron transactions
+  pub fn indefinite_array(&mut self) -> &mut Self {
+    self.data.push((MAJOR_ARRAY << 5) | 31);
+    self
+  }
+
+  pub fn end_indefinite(&mut self) -> &mut Self {
+    self.data.push(BREAK);
+    self
+  }
+
+  pub fn map(&mut self, length: usize) -> &mut Self {
+    self.header(MAJOR_MAP, length as u64)
+  }
+
+  pub fn tag(&mut self, tag: u64) -> &mut Self {
+    self.header(MAJOR_TAG, tag)
+  }
+
+  pub fn boolean(&mut self, value: bool) -> &mut Self {
+    self.data.push(if value { 0xf5 } else { 0xf4 });
+    self
+  }
+
+  pub fn null(&mut self) -> &mut Self {
+    self.data.push(0xf6);
+    self
+  }
+
+  // Already encoded item
+  pub fn raw(&mut self, item: &[u8]) -> &mut Self {
+    self.data.extend_from_slice(item);
+    self
+  }
+
+  pub fn into_bytes(self) -> Vec<u8> {
+    self.data
+  }
+}
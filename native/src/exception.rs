@@ -1,23 +1,153 @@
+use std::fmt;
 use std::panic;
 use std::result;
 use neon::prelude::*;
 
-pub type Result<T> = result::Result<T, String>;
+// Errors of the native layer. Thrown to JS as `Error` objects
+// with a stable `code` field and the variant fields as properties.
+#[derive(Debug)]
+pub enum Error {
+  InvalidLength { field: &'static str, actual: usize, expected: usize },
+  TooShort { field: &'static str, actual: usize, min: usize },
+  InvalidArgument(String),
+  BadPassword,
+  Decode(String),
+  OutputTooLarge { size: usize, max: usize },
+  KeyDestroyed,
+  MnemonicWordCount { words: usize },
+  MnemonicUnknownWord { position: usize },
+  MnemonicChecksum,
+  InvalidPath { position: usize, step: String },
+  HardenedPublicDerivation { position: usize },
+  InsufficientFunds { available: u64, needed: u64 },
+  TransactionTooLarge { size: usize, max: usize },
+  // Failures reported by the JSON based wallet_wasm functions
+  NotEnoughInput { loc: String, msg: String },
+  Wallet { loc: String, msg: String },
+  Native(String)
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl Error {
+  pub fn code(&self) -> &'static str {
+    match *self {
+      Error::InvalidLength { .. } => "ERR_INVALID_LENGTH",
+      Error::TooShort { .. } => "ERR_INVALID_LENGTH",
+      Error::InvalidArgument(_) => "ERR_INVALID_ARGUMENT",
+      Error::BadPassword => "ERR_BAD_PASSWORD",
+      Error::Decode(_) => "ERR_DECODE",
+      Error::OutputTooLarge { .. } => "ERR_OUTPUT_TOO_LARGE",
+      Error::KeyDestroyed => "ERR_KEY_DESTROYED",
+      Error::MnemonicWordCount { .. } => "ERR_MNEMONIC_WORD_COUNT",
+      Error::MnemonicUnknownWord { .. } => "ERR_MNEMONIC_UNKNOWN_WORD",
+      Error::MnemonicChecksum => "ERR_MNEMONIC_CHECKSUM",
+      Error::InvalidPath { .. } => "ERR_INVALID_PATH",
+      Error::HardenedPublicDerivation { .. } => "ERR_HARDENED_PUBLIC_DERIVATION",
+      Error::InsufficientFunds { .. } => "ERR_INSUFFICIENT_FUNDS",
+      Error::TransactionTooLarge { .. } => "ERR_TX_TOO_LARGE",
+      Error::NotEnoughInput { .. } => "ERR_INSUFFICIENT_FUNDS",
+      Error::Wallet { .. } => "ERR_WALLET",
+      Error::Native(_) => "ERR_NATIVE"
+    }
+  }
+
+  fn set_details<'a, C: Context<'a>>(&self, cx: &mut C, error: Handle<'a, JsError>) -> NeonResult<()> {
+    match *self {
+      Error::InvalidLength { field, actual, expected } => {
+        set_string(cx, error, "field", field)?;
+        set_number(cx, error, "actual", actual)?;
+        set_number(cx, error, "expected", expected)
+      },
+      Error::TooShort { field, actual, min } => {
+        set_string(cx, error, "field", field)?;
+        set_number(cx, error, "actual", actual)?;
+        set_number(cx, error, "min", min)
+      },
+      Error::OutputTooLarge { size, max } => {
+        set_number(cx, error, "size", size)?;
+        set_number(cx, error, "max", max)
+      },
+      Error::MnemonicWordCount { words } => set_number(cx, error, "words", words),
+      Error::MnemonicUnknownWord { position } => set_number(cx, error, "position", position),
+      Error::InvalidPath { position, ref step } => {
+        set_number(cx, error, "position", position)?;
+        set_string(cx, error, "step", step)
+      },
+      Error::HardenedPublicDerivation { position } => set_number(cx, error, "position", position),
+      // Lovelace amounts can exceed the safe integer range of JS numbers
+      Error::InsufficientFunds { available, needed } => {
+        set_string(cx, error, "available", &available.to_string())?;
+        set_string(cx, error, "needed", &needed.to_string())
+      },
+      Error::TransactionTooLarge { size, max } => {
+        set_number(cx, error, "size", size)?;
+        set_number(cx, error, "max", max)
+      },
+      Error::NotEnoughInput { ref loc, ref msg } | Error::Wallet { ref loc, ref msg } => {
+        set_string(cx, error, "loc", loc)?;
+        set_string(cx, error, "msg", msg)
+      },
+      _ => Ok(())
+    }
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Error::InvalidLength { field, actual, expected } =>
+        write!(f, "Wrong {} len {} should be {}", field, actual, expected),
+      Error::TooShort { field, actual, min } =>
+        write!(f, "Wrong {} len {} should be at least {}", field, actual, min),
+      Error::InvalidArgument(ref msg) => write!(f, "{}", msg),
+      Error::BadPassword => write!(f, "Decryption failed. Check your password."),
+      Error::Decode(ref msg) => write!(f, "{}", msg),
+      Error::OutputTooLarge { size, max } => write!(f, "Response {} >= {}", size, max),
+      Error::KeyDestroyed => write!(f, "Private key is destroyed"),
+      Error::MnemonicWordCount { words } =>
+        write!(f, "Wrong mnemonic word count {} should be 12, 15, 18, 21 or 24", words),
+      Error::MnemonicUnknownWord { position } =>
+        write!(f, "Mnemonic word at position {} is not in the dictionary", position),
+      Error::MnemonicChecksum => write!(f, "Invalid mnemonic checksum"),
+      Error::InvalidPath { position, ref step } =>
+        write!(f, "Invalid derivation path step {} at position {}", step, position),
+      Error::HardenedPublicDerivation { position } =>
+        write!(f, "Cannot do public derivation with hard index at position {}", position),
+      Error::InsufficientFunds { available, needed } =>
+        write!(f, "Not enough funds: {} available, {} needed", available, needed),
+      Error::TransactionTooLarge { size, max } => write!(f, "Transaction size {} > {}", size, max),
+      Error::NotEnoughInput { ref loc, ref msg } | Error::Wallet { ref loc, ref msg } =>
+        write!(f, "Error in: {}, message: {}", loc, msg),
+      Error::Native(ref msg) => write!(f, "{}", msg)
+    }
+  }
+}
+
+fn set_string<'a, C: Context<'a>>(cx: &mut C, error: Handle<'a, JsError>, key: &str, value: &str) -> NeonResult<()> {
+  let value = cx.string(value);
+  error.set(cx, key, value).map(|_| ())
+}
+
+fn set_number<'a, C: Context<'a>>(cx: &mut C, error: Handle<'a, JsError>, key: &str, value: usize) -> NeonResult<()> {
+  let value = cx.number(value as f64);
+  error.set(cx, key, value).map(|_| ())
+}
 
 pub trait NeonResultConvertible<T> {
     fn or_throw<'a, C: Context<'a>>(self, cx: &mut C) -> NeonResult<T>;
 }
 
-pub fn handle_exception<F: FnOnce() -> R + panic::UnwindSafe, R>(func: F) -> Result<R> {
+pub fn handle_exception<F: FnOnce() -> Result<R> + panic::UnwindSafe, R>(func: F) -> Result<R> {
   match panic::catch_unwind(func) {
-    Ok(res) => Ok(res),
+    Ok(res) => res,
     Err(err) => {
       if let Some(string) = err.downcast_ref::<String>() {
-        return Err(string.clone());
+        return Err(Error::Native(string.clone()));
       } else if let Some(string) = err.downcast_ref::<&'static str>() {
-        return Err(string.to_string());
+        return Err(Error::Native(string.to_string()));
       }
-      return Err(format!("Error: {:?}", err));
+      return Err(Error::Native(format!("Error: {:?}", err)));
     }
   }
 }
@@ -30,7 +160,13 @@ impl<T> NeonResultConvertible<T> for Result<T> {
   fn or_throw<'a, C: Context<'a>>(self, cx: &mut C) -> NeonResult<T> {
     match self {
       Ok(val) => Ok(val),
-      Err(err) => cx.throw_error(err)
+      Err(err) => {
+        let error = JsError::error(cx, err.to_string())?;
+        let code = cx.string(err.code());
+        error.set(cx, "code", code)?;
+        err.set_details(cx, error)?;
+        cx.throw(error)
+      }
     }
   }
 }
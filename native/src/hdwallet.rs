@@ -3,6 +3,77 @@ use exception::*;
 use buffer::*;
 use wallet_wasm;
 use cardano::hdwallet;
+use cryptoxide::ed25519;
+use std::mem;
+use std::ptr;
+
+pub const HARDENED_INDEX: u32 = 0x80000000;
+// Ed25519 public key, the first half of an XPub
+pub const PUBLIC_KEY_SIZE: usize = 32;
+
+// Parses a BIP32 path like m/44'/1815'/0'/0/12. The leading `m` is optional
+// and hardened steps can be marked with ', h or H.
+pub fn parse_path(path: &str) -> Result<Vec<u32>> {
+  let mut steps: Vec<&str> = path.trim().split('/').collect();
+  if steps[0] == "m" || steps[0] == "M" {
+    steps.remove(0);
+  }
+  if steps.len() == 1 && steps[0].is_empty() {
+    return Ok(Vec::new());
+  }
+  steps.iter().enumerate().map(|(position, step)| {
+    let (number, hardened) = match step.chars().last() {
+      Some('\'') | Some('h') | Some('H') => (&step[..step.len() - 1], true),
+      _ => (*step, false)
+    };
+    let index = number.parse::<u32>().ok()
+      .filter(|index| *index < HARDENED_INDEX)
+      .ok_or_else(|| Error::InvalidPath { position: position, step: step.to_string() })?;
+    Ok(if hardened { index + HARDENED_INDEX } else { index })
+  }).collect()
+}
+
+// Inverse of `parse_path`, hardened steps are marked with '
+pub fn format_path(indices: &[u32]) -> String {
+  let mut path = String::from("m");
+  for index in indices {
+    if *index >= HARDENED_INDEX {
+      path.push_str(&format!("/{}'", index - HARDENED_INDEX));
+    } else {
+      path.push_str(&format!("/{}", index));
+    }
+  }
+  path
+}
+
+// Derives the XPrv at `xprv` along `indices` into `output`. Intermediate keys are wiped.
+pub fn derive_private_indices(xprv: *const u8, indices: &[u32], output: *mut u8) {
+  let mut current = SecureBuffer::new(hdwallet::XPRV_SIZE);
+  let mut next = SecureBuffer::new(hdwallet::XPRV_SIZE);
+  unsafe { ptr::copy_nonoverlapping(xprv, current.as_mut_ptr(), hdwallet::XPRV_SIZE) };
+  for index in indices {
+    wallet_wasm::wallet_derive_private(current.as_ptr(), *index, next.as_mut_ptr());
+    mem::swap(&mut current, &mut next);
+  }
+  unsafe { ptr::copy_nonoverlapping(current.as_ptr(), output, hdwallet::XPRV_SIZE) };
+}
+
+pub fn derive_public_indices(xpub: *const u8, indices: &[u32], output: *mut u8) -> Result<()> {
+  if let Some(position) = indices.iter().position(|index| *index >= HARDENED_INDEX) {
+    return Err(Error::HardenedPublicDerivation { position: position });
+  }
+  let mut current = [0 as u8; hdwallet::XPUB_SIZE];
+  let mut next = [0 as u8; hdwallet::XPUB_SIZE];
+  unsafe { ptr::copy_nonoverlapping(xpub, current.as_mut_ptr(), hdwallet::XPUB_SIZE) };
+  for index in indices {
+    if !wallet_wasm::wallet_derive_public(current.as_ptr(), *index, next.as_mut_ptr()) {
+      return Err(Error::Native(String::from("Can't derive public key")));
+    }
+    mem::swap(&mut current, &mut next);
+  }
+  unsafe { ptr::copy_nonoverlapping(current.as_ptr(), output, hdwallet::XPUB_SIZE) };
+  Ok(())
+}
 
 // Params: entropy: Buffer, password: Buffer
 pub fn from_enhanced_entropy(mut cx: FunctionContext) -> JsResult<JsBuffer> {
@@ -21,7 +92,10 @@ pub fn from_enhanced_entropy(mut cx: FunctionContext) -> JsResult<JsBuffer> {
         entropy_buf.ptr, entropy_buf.size, pwd_buf.ptr, pwd_buf.size, output.ptr
       );
 
-      if res != 0 { panic!("Rust method error. Check entropy size.") }
+      if res != 0 {
+        return Err(Error::InvalidArgument(format!("Rust method error. Check entropy size {}.", entropy_buf.size)));
+      }
+      Ok(())
     }).map(|_| output_buf )
   }.or_throw(&mut cx)
 }
@@ -37,10 +111,11 @@ pub fn from_seed(mut cx: FunctionContext) -> JsResult<JsBuffer> {
     let output: MutBufferPtr = output_buf.borrow_mut(&guard).into();
 
     handle_exception(|| {
-      if seed_buf.size != hdwallet::SEED_SIZE { 
-        panic!("Wrong seed len {} should be {}", seed_buf.size, hdwallet::SEED_SIZE);
+      if seed_buf.size != hdwallet::SEED_SIZE {
+        return Err(Error::InvalidLength { field: "seed", actual: seed_buf.size, expected: hdwallet::SEED_SIZE });
       }
       wallet_wasm::wallet_from_seed(seed_buf.ptr, output.ptr);
+      Ok(())
     }).map(|_| output_buf )
   }.or_throw(&mut cx)
 }
@@ -56,10 +131,11 @@ pub fn to_public(mut cx: FunctionContext) -> JsResult<JsBuffer> {
     let output: MutBufferPtr = output_buf.borrow_mut(&guard).into();
 
     handle_exception(|| {
-      if xprv_buf.size != hdwallet::XPRV_SIZE { 
-        panic!("Wrong XPrv len {} should be {}", xprv_buf.size, hdwallet::XPRV_SIZE);
+      if xprv_buf.size != hdwallet::XPRV_SIZE {
+        return Err(Error::InvalidLength { field: "XPrv", actual: xprv_buf.size, expected: hdwallet::XPRV_SIZE });
       }
       wallet_wasm::wallet_to_public(xprv_buf.ptr, output.ptr);
+      Ok(())
     }).map(|_| output_buf )
   }.or_throw(&mut cx)
 }
@@ -76,10 +152,11 @@ pub fn derive_private(mut cx: FunctionContext) -> JsResult<JsBuffer> {
     let output: MutBufferPtr = output_buf.borrow_mut(&guard).into();
 
     handle_exception(|| {
-      if xprv_buf.size != hdwallet::XPRV_SIZE { 
-        panic!("Wrong XPrv len {} should be {}", xprv_buf.size, hdwallet::XPRV_SIZE);
+      if xprv_buf.size != hdwallet::XPRV_SIZE {
+        return Err(Error::InvalidLength { field: "XPrv", actual: xprv_buf.size, expected: hdwallet::XPRV_SIZE });
       }
       wallet_wasm::wallet_derive_private(xprv_buf.ptr, index, output.ptr);
+      Ok(())
     }).map(|_| output_buf )
   }.or_throw(&mut cx)
 }
@@ -96,15 +173,16 @@ pub fn derive_public(mut cx: FunctionContext) -> JsResult<JsBuffer> {
     let output: MutBufferPtr = output_buf.borrow_mut(&guard).into();
 
     handle_exception(|| {
-      if xpub_buf.size != hdwallet::XPUB_SIZE { 
-        panic!("Wrong XPub len {} should be {}", xpub_buf.size, hdwallet::XPUB_SIZE);
+      if xpub_buf.size != hdwallet::XPUB_SIZE {
+        return Err(Error::InvalidLength { field: "XPub", actual: xpub_buf.size, expected: hdwallet::XPUB_SIZE });
       }
-      if index >= 0x80000000 {
-        panic!("Cannot do public derivation with hard index");
+      if index >= HARDENED_INDEX {
+        return Err(Error::HardenedPublicDerivation { position: 0 });
       }
       let res = wallet_wasm::wallet_derive_public(xpub_buf.ptr, index, output.ptr);
 
-      if !res { panic!("Can't derive public key"); }
+      if !res { return Err(Error::Native(String::from("Can't derive public key"))); }
+      Ok(())
     }).map(|_| output_buf )
   }.or_throw(&mut cx)
 }
@@ -122,10 +200,103 @@ pub fn sign(mut cx: FunctionContext) -> JsResult<JsBuffer> {
     let output: MutBufferPtr = output_buf.borrow_mut(&guard).into();
 
     handle_exception(|| {
-      if xprv_buf.size != hdwallet::XPRV_SIZE { 
-        panic!("Wrong XPrv len {} should be {}", xprv_buf.size, hdwallet::XPRV_SIZE);
+      if xprv_buf.size != hdwallet::XPRV_SIZE {
+        return Err(Error::InvalidLength { field: "XPrv", actual: xprv_buf.size, expected: hdwallet::XPRV_SIZE });
       }
       wallet_wasm::wallet_sign(xprv_buf.ptr, data_buf.ptr, data_buf.size, output.ptr);
+      Ok(())
+    }).map(|_| output_buf )
+  }.or_throw(&mut cx)
+}
+
+pub fn verify_signature(public_key: &[u8], data: &[u8], signature: &[u8]) -> Result<bool> {
+  if signature.len() != hdwallet::SIGNATURE_SIZE {
+    return Err(Error::InvalidLength { field: "signature", actual: signature.len(), expected: hdwallet::SIGNATURE_SIZE });
+  }
+  Ok(ed25519::verify(data, &public_key[..PUBLIC_KEY_SIZE], signature))
+}
+
+// Params: xpub: Buffer, data: Buffer, signature: Buffer
+pub fn verify(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+  let xpub = cx.argument::<JsBuffer>(0)?;
+  let data = cx.argument::<JsBuffer>(1)?;
+  let signature = cx.argument::<JsBuffer>(2)?;
+
+  let valid = {
+    let guard = cx.lock();
+    let xpub_buf = xpub.borrow(&guard);
+    let data_buf = data.borrow(&guard);
+    let signature_buf = signature.borrow(&guard);
+
+    if xpub_buf.len() != hdwallet::XPUB_SIZE {
+      Err(Error::InvalidLength { field: "XPub", actual: xpub_buf.len(), expected: hdwallet::XPUB_SIZE })
+    } else {
+      verify_signature(xpub_buf.as_slice::<u8>(), data_buf.as_slice::<u8>(), signature_buf.as_slice::<u8>())
+    }
+  }.or_throw(&mut cx)?;
+  Ok(cx.boolean(valid))
+}
+
+// Params: publicKey: Buffer, data: Buffer, signature: Buffer
+pub fn verify_public_key(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+  let public_key = cx.argument::<JsBuffer>(0)?;
+  let data = cx.argument::<JsBuffer>(1)?;
+  let signature = cx.argument::<JsBuffer>(2)?;
+
+  let valid = {
+    let guard = cx.lock();
+    let public_key_buf = public_key.borrow(&guard);
+    let data_buf = data.borrow(&guard);
+    let signature_buf = signature.borrow(&guard);
+
+    if public_key_buf.len() != PUBLIC_KEY_SIZE {
+      Err(Error::InvalidLength { field: "public key", actual: public_key_buf.len(), expected: PUBLIC_KEY_SIZE })
+    } else {
+      verify_signature(public_key_buf.as_slice::<u8>(), data_buf.as_slice::<u8>(), signature_buf.as_slice::<u8>())
+    }
+  }.or_throw(&mut cx)?;
+  Ok(cx.boolean(valid))
+}
+
+// Params: xprv: Buffer, path: String
+pub fn derive_private_path(mut cx: FunctionContext) -> JsResult<JsBuffer> {
+  let xprv = cx.argument::<JsBuffer>(0)?;
+  let path = cx.argument::<JsString>(1)?.value();
+  let mut output_buf = cx.buffer(hdwallet::XPRV_SIZE as u32)?;
+
+  {
+    let guard = cx.lock();
+    let xprv_buf: BufferPtr = xprv.borrow(&guard).into();
+    let output: MutBufferPtr = output_buf.borrow_mut(&guard).into();
+
+    handle_exception(|| {
+      if xprv_buf.size != hdwallet::XPRV_SIZE {
+        return Err(Error::InvalidLength { field: "XPrv", actual: xprv_buf.size, expected: hdwallet::XPRV_SIZE });
+      }
+      let indices = parse_path(&path)?;
+      derive_private_indices(xprv_buf.ptr, &indices, output.ptr);
+      Ok(())
     }).map(|_| output_buf )
   }.or_throw(&mut cx)
-}
\ No newline at end of file
+}
+
+// Params: xpub: Buffer, path: String
+pub fn derive_public_path(mut cx: FunctionContext) -> JsResult<JsBuffer> {
+  let xpub = cx.argument::<JsBuffer>(0)?;
+  let path = cx.argument::<JsString>(1)?.value();
+  let mut output_buf = cx.buffer(hdwallet::XPUB_SIZE as u32)?;
+
+  {
+    let guard = cx.lock();
+    let xpub_buf: BufferPtr = xpub.borrow(&guard).into();
+    let output: MutBufferPtr = output_buf.borrow_mut(&guard).into();
+
+    handle_exception(|| {
+      if xpub_buf.size != hdwallet::XPUB_SIZE {
+        return Err(Error::InvalidLength { field: "XPub", actual: xpub_buf.size, expected: hdwallet::XPUB_SIZE });
+      }
+      let indices = parse_path(&path)?;
+      derive_public_indices(xpub_buf.ptr, &indices, output.ptr)
+    }).map(|_| output_buf )
+  }.or_throw(&mut cx)
+}
@@ -0,0 +1,116 @@
+use exception::*;
+use buffer::*;
+use std::str;
+
+// Signature shared by the JSON based wallet_wasm exports
+pub type JsonFunc = extern "C" fn(*const u8, usize, *mut u8) -> i32;
+
+// Failure part of a wallet_wasm response, `result` is left to JS
+#[derive(Deserialize)]
+struct JsonStatus {
+  #[serde(default)]
+  failed: bool,
+  #[serde(default)]
+  loc: String,
+  #[serde(default)]
+  msg: String
+}
+
+// wallet_wasm reports its failures with the debug form of the error, which starts
+// with the variant name. Only variants known by name get a specific code.
+fn wallet_error(loc: String, msg: String) -> Error {
+  let variant = msg.split(|char: char| !char.is_alphanumeric() && char != '_').next().unwrap_or("");
+  match variant {
+    "NotEnoughInput" | "TxNotEnoughTotalInput" => Error::NotEnoughInput { loc: loc, msg: msg },
+    _ => Error::Wallet { loc: loc, msg: msg }
+  }
+}
+
+// wallet_wasm writes its response into a caller provided buffer without bounds checks,
+// so `capacity` must be an upper bound computed from the request itself, checked by
+// the guard of the buffer.
+// The response is returned trimmed to its actual size, failures are returned as errors.
+pub fn call_json(func: JsonFunc, params: &str, capacity: usize) -> Result<SecureString> {
+  let mut output = GuardedBuffer::new(capacity);
+  let output_ptr = output.as_mut_ptr();
+
+  handle_exception(|| {
+    let params_ptr: &[u8] = params.as_bytes();
+
+    Ok(func(params_ptr.as_ptr(), params_ptr.len(), output_ptr))
+  }).and_then(|rsz| output.response(rsz)).and_then(|response| {
+    str::from_utf8(response)
+      .map(|string| SecureString::from(String::from(string)))
+      .map_err(|_| Error::Native(String::from("Response is not a valid UTF-8 string")))
+  }).and_then(|response| {
+    let status: JsonStatus = ::serde_json::from_str(&response)
+      .map_err(|err| Error::Native(format!("Invalid response: {}", err)))?;
+    if status.failed {
+      return Err(wallet_error(status.loc, status.msg));
+    }
+    Ok(response)
+  })
+}
+
+// Checks a count passed from JS against the number of items in the request
+pub fn check_count(value: f64, actual: usize, field: &'static str) -> Result<()> {
+  if value != actual as f64 {
+    return Err(Error::InvalidArgument(format!("Wrong {} count {} should be {}", field, value, actual)));
+  }
+  Ok(())
+}
+
+pub fn parse_request<'a, T: ::serde::Deserialize<'a>>(params: &'a str) -> Result<T> {
+  ::serde_json::from_str(params).map_err(|err| Error::Decode(format!("Invalid request: {}", err)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::ptr;
+
+  const RESPONSE: &str = "{\"failed\":false,\"loc\":\"\",\"msg\":\"\",\"result\":true}";
+  const FAILURE: &str = "{\"failed\":true,\"loc\":\"lib.rs:10\",\"msg\":\"NotEnoughInput\",\"result\":null}";
+
+  fn write(output: *mut u8, response: &str) -> i32 {
+    unsafe { ptr::copy_nonoverlapping(response.as_ptr(), output, response.len()) };
+    response.len() as i32
+  }
+
+  extern "C" fn respond(_: *const u8, _: usize, output: *mut u8) -> i32 {
+    write(output, RESPONSE)
+  }
+
+  extern "C" fn fail(_: *const u8, _: usize, output: *mut u8) -> i32 {
+    write(output, FAILURE)
+  }
+
+  #[test]
+  fn response_fitting_the_capacity() {
+    let response = call_json(respond, "{}", RESPONSE.len()).unwrap();
+    assert_eq!(&*response, RESPONSE);
+  }
+
+  #[test]
+  fn response_past_the_capacity_hits_the_guard() {
+    match call_json(respond, "{}", RESPONSE.len() - 1) {
+      Err(Error::OutputTooLarge { size, max }) => {
+        assert_eq!(size, RESPONSE.len());
+        assert_eq!(max, RESPONSE.len() - 1);
+      },
+      other => panic!("Unexpected result {:?}", other.map(|_| ()))
+    }
+  }
+
+  #[test]
+  fn failures_are_mapped_by_variant() {
+    match call_json(fail, "{}", FAILURE.len()) {
+      Err(Error::NotEnoughInput { loc, .. }) => assert_eq!(loc, "lib.rs:10"),
+      other => panic!("Unexpected result {:?}", other.map(|_| ()))
+    }
+    match wallet_error(String::from("lib.rs:10"), String::from("invalid hex in decoded input")) {
+      Error::Wallet { .. } => (),
+      other => panic!("Unexpected error {:?}", other)
+    }
+  }
+}
@@ -2,15 +2,42 @@
 extern crate neon;
 
 extern crate cardano;
+extern crate cbor_event;
 extern crate wallet_wasm;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate cryptoxide;
+extern crate rand;
+#[macro_use]
+extern crate lazy_static;
+extern crate unicode_normalization;
+extern crate num_cpus;
+extern crate bech32;
 
 mod password_protect;
 mod random_checker;
+mod random_address;
 mod exception;
 mod buffer;
 mod hdwallet;
+mod address;
+mod cbor;
+mod params;
+mod selection;
+mod shelley;
+mod shelley_tx;
+mod byron_tx;
+mod migration;
+mod transaction;
+mod private_key;
 mod wallet;
+mod mnemonic;
+mod json;
+mod task;
 
+// Capacity of responses which don't grow with the request
 pub const MAX_OUTPUT_SIZE: usize = 4096;
 
 use neon::prelude::*;
@@ -26,18 +53,64 @@ register_module!(mut cx, {
     cx.export_function("password_protect_decrypt_with_password", password_protect::decrypt_with_password)?;
     cx.export_function("random_checker_new_checker", random_checker::new_checker)?;
     cx.export_function("random_checker_new_checker_from_mnemonics", random_checker::new_checker_from_mnemonics)?;
-    cx.export_function("random_checker_check_addresses", random_checker::check_addresses)?;
+    cx.export_function("random_checker_check_addresses", random_checker::check_addresses_json)?;
+    cx.export_function("random_checker_check_addresses_async", random_checker::check_addresses_json_async)?;
+    cx.export_class::<random_checker::JsRandomAddressChecker>("RandomAddressChecker")?;
+    cx.export_function("random_address_generate", random_address::generate_address)?;
     cx.export_function("hdwallet_from_enhanced_entropy", hdwallet::from_enhanced_entropy)?;
     cx.export_function("hdwallet_from_seed", hdwallet::from_seed)?;
     cx.export_function("hdwallet_to_public", hdwallet::to_public)?;
     cx.export_function("hdwallet_derive_private", hdwallet::derive_private)?;
     cx.export_function("hdwallet_derive_public", hdwallet::derive_public)?;
+    cx.export_function("hdwallet_derive_private_path", hdwallet::derive_private_path)?;
+    cx.export_function("hdwallet_derive_public_path", hdwallet::derive_public_path)?;
     cx.export_function("hdwallet_sign", hdwallet::sign)?;
+    cx.export_function("hdwallet_verify", hdwallet::verify)?;
+    cx.export_function("hdwallet_verify_public_key", hdwallet::verify_public_key)?;
+    cx.export_function("address_derive_range", address::derive_range)?;
+    cx.export_function("address_derive_range_async", address::derive_range_async)?;
+    cx.export_function("address_decode", address::decode)?;
+    cx.export_function("address_validate", address::validate)?;
+    cx.export_function("shelley_base_address", shelley::base_address)?;
+    cx.export_function("shelley_pointer_address", shelley::pointer_address)?;
+    cx.export_function("shelley_enterprise_address", shelley::enterprise_address)?;
+    cx.export_function("shelley_reward_address", shelley::reward_address)?;
+    cx.export_function("shelley_decode_address", shelley::decode_address)?;
+    cx.export_function("shelley_account_addresses", shelley::account_addresses)?;
+    cx.export_function("shelley_account_xpub", shelley::account_xpub)?;
+    cx.export_function("shelley_stake_key", shelley::stake_key)?;
+    cx.export_function("shelley_tx_spend", shelley_tx::spend)?;
+    cx.export_function("shelley_tx_build_unsigned", shelley_tx::build_unsigned)?;
+    cx.export_function("shelley_tx_sign", shelley_tx::sign)?;
+    cx.export_function("byron_tx_estimate_fee", byron_tx::estimate_fee)?;
+    cx.export_function("byron_tx_spend", byron_tx::spend)?;
+    cx.export_function("byron_tx_spend_async", byron_tx::spend_async)?;
+    cx.export_function("byron_tx_drain", byron_tx::drain_func)?;
+    cx.export_function("byron_tx_drain_async", byron_tx::drain_async)?;
+    cx.export_function("byron_tx_move", byron_tx::move_func)?;
+    cx.export_function("byron_tx_move_async", byron_tx::move_async)?;
+    cx.export_function("migration_migrate", migration::migrate)?;
+    cx.export_function("migration_migrate_async", migration::migrate_async)?;
+    cx.export_function("transaction_decode", transaction::decode_transaction)?;
+    cx.export_function("transaction_verify", transaction::verify_transaction)?;
+    cx.export_class::<private_key::JsPrivateKey>("PrivateKey")?;
+    cx.export_function("private_key_from_enhanced_entropy", private_key::from_enhanced_entropy)?;
+    cx.export_function("private_key_from_seed", private_key::from_seed)?;
+    cx.export_function("private_key_from_xprv", private_key::from_xprv)?;
+    cx.export_function("mnemonic_generate", mnemonic::generate)?;
+    cx.export_function("mnemonic_validate", mnemonic::validate)?;
+    cx.export_function("mnemonic_to_entropy", mnemonic::to_entropy)?;
+    cx.export_function("mnemonic_from_entropy", mnemonic::from_entropy)?;
+    cx.export_function("mnemonic_suggest", mnemonic::suggest)?;
+    cx.export_function("mnemonic_detect_language", mnemonic::detect_language)?;
     cx.export_function("wallet_from_master_key", wallet::from_master_key)?;
     cx.export_function("wallet_from_daedalus_mnemonic", wallet::from_daedalus_mnemonic)?;
     cx.export_function("wallet_new_account", wallet::new_account)?;
     cx.export_function("wallet_generate_addresses", wallet::generate_addresses)?;
+    cx.export_function("wallet_generate_addresses_async", wallet::generate_addresses_async)?;
     cx.export_function("wallet_check_address", wallet::check_address)?;
     cx.export_function("wallet_spend", wallet::spend)?;
-    cx.export_function("wallet_move", wallet::move_func)
+    cx.export_function("wallet_spend_async", wallet::spend_async)?;
+    cx.export_function("wallet_move", wallet::move_func)?;
+    cx.export_function("wallet_move_async", wallet::move_async)
 });
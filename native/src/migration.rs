@@ -0,0 +1,293 @@
+use neon::prelude::*;
+use exception::*;
+use json::*;
+use params::ProtocolParams;
+use address::{decode_byron, is_bech32_era};
+use hdwallet::{HARDENED_INDEX, PUBLIC_KEY_SIZE};
+use shelley::{self, ShelleyAddress};
+use shelley_tx::{
+  encode_bootstrap_witnesses, encode_transaction, parse_coin, set_bytes, set_coin, sum_coins, tx_hash, Addressing,
+  BootstrapWitness, OutputRequest, Plan, TxPointer, MAX_FEE_ROUNDS, TX_ID_SIZE
+};
+use byron_tx::{byron_address, derive_keys, set_pointers, tx_pointer, ByronWallet};
+use task::{RequestTask, ToJs};
+use cardano::hdwallet::{self, Signature};
+use cardano::util::hex;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::mem;
+
+// Byron inputs come from Yoroi BIP44 wallets or Daedalus random addresses
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ByronAddressing {
+  Bip44(Addressing),
+  // Hardened `[account, index]` of a random address
+  Random([u32; 2])
+}
+
+impl ByronAddressing {
+  fn path(&self) -> Result<Vec<u32>> {
+    match *self {
+      ByronAddressing::Bip44(ref addressing) => {
+        if addressing.account >= HARDENED_INDEX {
+          return Err(Error::InvalidArgument(format!("Wrong account {} should be below {}", addressing.account, HARDENED_INDEX)));
+        }
+        Ok(vec![HARDENED_INDEX | addressing.account, addressing.change, addressing.index])
+      },
+      ByronAddressing::Random(indices) => Ok(indices.to_vec())
+    }
+  }
+}
+
+#[derive(Deserialize)]
+struct MigrationInput {
+  ptr: TxPointer,
+  value: OutputRequest,
+  addressing: ByronAddressing
+}
+
+#[derive(Deserialize)]
+struct MigrationRequest {
+  wallet: ByronWallet,
+  inputs: Vec<MigrationInput>,
+  output: String,
+  ttl: u64,
+  #[serde(default)]
+  protocol_params: Option<ProtocolParams>
+}
+
+struct Spendable {
+  pointer: (Vec<u8>, u32),
+  value: u64,
+  path: Vec<u32>,
+  // Attributes of the Byron address, repeated in the bootstrap witness
+  attributes: Vec<u8>
+}
+
+// Transaction of the migration, spending the inputs at `inputs` to the output
+struct Migration {
+  inputs: Vec<usize>,
+  value: u64,
+  fee: u64
+}
+
+struct SignedMigration {
+  tx: Vec<u8>,
+  tx_id: [u8; TX_ID_SIZE],
+  fee: u64,
+  value: u64,
+  selected: Vec<(String, u32)>
+}
+
+struct MigrationResult {
+  transactions: Vec<SignedMigration>,
+  skipped: Vec<(String, u32)>
+}
+
+impl ToJs for MigrationResult {
+  fn to_js<'a, C: Context<'a>>(&self, cx: &mut C) -> JsResult<'a, JsObject> {
+    let object = cx.empty_object();
+    let array = JsArray::new(cx, self.transactions.len() as u32);
+    let (mut total_fee, mut total_value) = (0 as u64, 0 as u64);
+    for (position, signed) in self.transactions.iter().enumerate() {
+      let transaction = cx.empty_object();
+      set_bytes(cx, transaction, "cbor_encoded_tx", &signed.tx)?;
+      let tx_id = cx.string(hex::encode(&signed.tx_id));
+      transaction.set(cx, "tx_id", tx_id)?;
+      set_coin(cx, transaction, "fee", signed.fee)?;
+      set_coin(cx, transaction, "value", signed.value)?;
+      set_pointers(cx, transaction, "selected_inputs", &signed.selected)?;
+      array.set(cx, position as u32, transaction)?;
+      total_fee += signed.fee;
+      total_value += signed.value;
+    }
+    object.set(cx, "transactions", array)?;
+    set_pointers(cx, object, "skipped_inputs", &self.skipped)?;
+    set_coin(cx, object, "fee", total_fee)?;
+    set_coin(cx, object, "value", total_value)?;
+    Ok(object)
+  }
+}
+
+fn spendable(input: &MigrationInput) -> Result<Spendable> {
+  let address = byron_address(&input.value.address)?;
+  let attributes = decode_byron(&address)?.attributes;
+  Ok(Spendable {
+    pointer: tx_pointer(&input.ptr)?, value: parse_coin(&input.value.value)?, path: input.addressing.path()?,
+    attributes: attributes
+  })
+}
+
+// Funds can only move to payment addresses, reward addresses hold no UTxO
+fn shelley_output(address: &str) -> Result<Vec<u8>> {
+  if !is_bech32_era(address) {
+    return Err(Error::InvalidArgument(format!("{} is not a Shelley address", address)));
+  }
+  match shelley::decode(address)? {
+    ShelleyAddress::Reward { .. } => Err(Error::InvalidArgument(format!("Can't migrate to the reward address {}", address))),
+    decoded => Ok(decoded.to_bytes())
+  }
+}
+
+// One witness per key, with the attributes of its address
+fn witness_keys(batch: &[&Spendable]) -> BTreeMap<Vec<u32>, Vec<u8>> {
+  batch.iter().map(|input| (input.path.clone(), input.attributes.clone())).collect()
+}
+
+fn encode_body(batch: &[&Spendable], output: &[u8], value: u64, fee: u64, ttl: u64) -> Vec<u8> {
+  let inputs = batch.iter().map(|input| input.pointer.clone()).collect();
+  Plan::payment(inputs, vec![(output.to_vec(), value)], fee, ttl).encode_body()
+}
+
+// Size of the signed transaction, placeholder witnesses have the size of real ones
+fn signed_size(batch: &[&Spendable], output: &[u8], value: u64, fee: u64, ttl: u64) -> usize {
+  let witnesses: Vec<BootstrapWitness> = witness_keys(batch).into_iter().map(|(_, attributes)| BootstrapWitness {
+    public_key: vec![0; PUBLIC_KEY_SIZE], signature: vec![0; hdwallet::SIGNATURE_SIZE],
+    chain_code: vec![0; hdwallet::XPUB_SIZE - PUBLIC_KEY_SIZE], attributes: attributes
+  }).collect();
+  encode_transaction(&encode_body(batch, output, value, fee, ttl), &encode_bootstrap_witnesses(&witnesses)).len()
+}
+
+// Sends the whole batch to `output`, the fee being taken from that output
+fn drain(batch: &[&Spendable], output: &[u8], ttl: u64, params: &ProtocolParams) -> Result<(u64, u64)> {
+  let available = sum_coins(batch.iter().map(|input| input.value))?;
+  let mut fee = params.fee_constant;
+  for _ in 0..MAX_FEE_ROUNDS {
+    let needed = fee.saturating_add(cmp::max(params.min_utxo, 1));
+    if available < needed {
+      return Err(Error::InsufficientFunds { available: available, needed: needed });
+    }
+    let value = available - fee;
+    let size = signed_size(batch, output, value, fee, ttl);
+    if size > params.max_tx_size {
+      return Err(Error::TransactionTooLarge { size: size, max: params.max_tx_size });
+    }
+    let required = params.min_fee(size);
+    if required <= fee {
+      return Ok((value, fee));
+    }
+    fee = required;
+  }
+  Err(Error::Native(String::from("Fee calculation didn't converge")))
+}
+
+// Packs the inputs, largest first, into as few transactions as the size limit
+// allows. Sizes are checked with the widest fee and value so that no transaction
+// grows past the limit once they are known. Inputs too large to be spent even alone
+// and transactions which can't pay for themselves are dropped, their inputs
+// returned as skipped.
+fn plan(
+  inputs: &[Spendable], output: &[u8], ttl: u64, params: &ProtocolParams
+) -> Result<(Vec<Migration>, Vec<usize>)> {
+  let mut order: Vec<usize> = (0..inputs.len()).collect();
+  order.sort_by(|a, b| inputs[*b].value.cmp(&inputs[*a].value));
+
+  let widest_size = |batch: &[usize]| {
+    let spent: Vec<&Spendable> = batch.iter().map(|position| &inputs[*position]).collect();
+    signed_size(&spent, output, u64::max_value(), u64::max_value(), ttl)
+  };
+
+  let mut batches: Vec<Vec<usize>> = Vec::new();
+  let mut batch: Vec<usize> = Vec::new();
+  let mut skipped = Vec::new();
+  // Why nothing could be migrated when every input ends up skipped
+  let mut failure = None;
+  for position in order {
+    let size = widest_size(&[position]);
+    if size > params.max_tx_size {
+      failure = Some(Error::TransactionTooLarge { size: size, max: params.max_tx_size });
+      skipped.push(position);
+      continue;
+    }
+    batch.push(position);
+    if batch.len() > 1 && widest_size(&batch) > params.max_tx_size {
+      batch.pop();
+      batches.push(mem::replace(&mut batch, vec![position]));
+    }
+  }
+  if !batch.is_empty() {
+    batches.push(batch);
+  }
+
+  let mut migrations = Vec::new();
+  for batch in batches {
+    let spent: Vec<&Spendable> = batch.iter().map(|position| &inputs[*position]).collect();
+    match drain(&spent, output, ttl, params) {
+      Ok((value, fee)) => migrations.push(Migration { inputs: batch, value: value, fee: fee }),
+      Err(Error::InsufficientFunds { available, needed }) => {
+        failure = Some(Error::InsufficientFunds { available: available, needed: needed });
+        skipped.extend(batch);
+      },
+      Err(err) => return Err(err)
+    }
+  }
+  match failure {
+    Some(err) if migrations.is_empty() => Err(err),
+    _ => Ok((migrations, skipped))
+  }
+}
+
+// Signs the body hash with the Byron keys of the spent addresses
+fn sign(
+  wallet: &ByronWallet, inputs: &[Spendable], migration: &Migration, output: &[u8], ttl: u64
+) -> Result<(Vec<u8>, [u8; TX_ID_SIZE])> {
+  let batch: Vec<&Spendable> = migration.inputs.iter().map(|position| &inputs[*position]).collect();
+  let body = encode_body(&batch, output, migration.value, migration.fee, ttl);
+  let hash = tx_hash(&body);
+  let keys = witness_keys(&batch);
+  let paths: Vec<Vec<u32>> = keys.keys().cloned().collect();
+  let witnesses = derive_keys(wallet, &paths)?.iter().zip(keys.values()).map(|(key, attributes)| {
+    let signature: Signature<()> = key.sign(&hash);
+    let xpub = key.public();
+    BootstrapWitness {
+      public_key: xpub.as_ref()[..PUBLIC_KEY_SIZE].to_vec(), signature: signature.as_ref().to_vec(),
+      chain_code: xpub.as_ref()[PUBLIC_KEY_SIZE..].to_vec(), attributes: attributes.clone()
+    }
+  }).collect::<Vec<_>>();
+  Ok((encode_transaction(&body, &encode_bootstrap_witnesses(&witnesses)), hash))
+}
+
+fn migrate_tx(params: &str) -> Result<MigrationResult> {
+  let request = parse_request::<MigrationRequest>(params)?;
+  if request.inputs.is_empty() {
+    return Err(Error::InvalidArgument(String::from("Transaction has no inputs")));
+  }
+  let params = request.protocol_params.unwrap_or_else(ProtocolParams::shelley);
+  let inputs = request.inputs.iter().map(spendable).collect::<Result<Vec<_>>>()?;
+  let output = shelley_output(&request.output)?;
+  let (migrations, skipped) = plan(&inputs, &output, request.ttl, &params)?;
+  let pointers = |positions: &[usize]| positions.iter().map(|position| {
+    (request.inputs[*position].ptr.id.clone(), request.inputs[*position].ptr.index)
+  }).collect::<Vec<_>>();
+  let mut transactions = Vec::with_capacity(migrations.len());
+  for migration in migrations.iter() {
+    let (tx, tx_id) = sign(&request.wallet, &inputs, migration, &output, request.ttl)?;
+    transactions.push(SignedMigration {
+      tx: tx, tx_id: tx_id, fee: migration.fee, value: migration.value, selected: pointers(&migration.inputs)
+    });
+  }
+  Ok(MigrationResult { transactions: transactions, skipped: pointers(&skipped) })
+}
+
+// Moves Byron funds, from BIP44 or random addresses, to a Shelley address with as
+// few Shelley era transactions as the size limit allows.
+// Params: params: JSONString { wallet, inputs, output, ttl, protocol_params? }
+// Returns: { transactions: Array<{ cbor_encoded_tx: Buffer, tx_id: String, fee: String, value: String,
+//            selected_inputs: Array<{ id, index }> }>, skipped_inputs: Array<{ id, index }>,
+//            fee: String, value: String }
+pub fn migrate(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+
+  let result = handle_exception(|| migrate_tx(&params)).or_throw(&mut cx)?;
+  result.to_js(&mut cx)
+}
+
+// Params: params: JSONString, callback: Function
+pub fn migrate_async(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let callback = cx.argument::<JsFunction>(1)?;
+
+  RequestTask::new(migrate_tx, params).schedule(callback);
+  Ok(cx.undefined())
+}
@@ -0,0 +1,267 @@
+use neon::prelude::*;
+use exception::*;
+use buffer::*;
+use std::collections::HashMap;
+use cardano::bip::bip39::dictionary;
+use cryptoxide::digest::Digest;
+use cryptoxide::sha2::Sha256;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use unicode_normalization::UnicodeNormalization;
+
+// Each word encodes 11 bits, every 3 words carry 4 bytes of entropy and 1 bit of checksum
+const WORD_BITS: usize = 11;
+
+// BIP39 dictionary with NFKD normalized words for lookups
+struct Language {
+  name: &'static str,
+  separator: &'static str,
+  words: Vec<&'static str>,
+  normalized: Vec<String>,
+  indices: HashMap<String, u16>,
+  max_word_len: usize
+}
+
+impl Language {
+  fn new(name: &'static str, separator: &'static str, dic: &dictionary::DefaultDictionary) -> Self {
+    let words = dic.words.to_vec();
+    let normalized: Vec<String> = words.iter().map(|word| word.nfkd().collect()).collect();
+    let indices = normalized.iter().enumerate()
+      .map(|(index, word)| (word.clone(), index as u16))
+      .collect();
+    let max_word_len = words.iter().map(|word| word.len()).max().unwrap_or(0);
+    Language {
+      name: name, separator: separator, words: words,
+      normalized: normalized, indices: indices, max_word_len: max_word_len
+    }
+  }
+
+  // Expects a NFKD normalized word
+  fn lookup(&self, word: &str) -> Option<u16> {
+    self.indices.get(word).cloned()
+  }
+}
+
+lazy_static! {
+  // In auto detection order. Shared words resolve to the first valid language.
+  static ref LANGUAGES: Vec<Language> = vec![
+    Language::new("english", " ", &dictionary::ENGLISH),
+    Language::new("japanese", "\u{3000}", &dictionary::JAPANESE),
+    Language::new("spanish", " ", &dictionary::SPANISH),
+    Language::new("french", " ", &dictionary::FRENCH),
+    Language::new("italian", " ", &dictionary::ITALIAN),
+    Language::new("korean", " ", &dictionary::KOREAN),
+    Language::new("chinese_simplified", " ", &dictionary::CHINESE_SIMPLIFIED),
+    Language::new("chinese_traditional", " ", &dictionary::CHINESE_TRADITIONAL)
+  ];
+}
+
+// An empty name selects English when creating a phrase and auto detection when reading one
+fn find_language(name: &str) -> Result<Option<&'static Language>> {
+  if name.is_empty() {
+    return Ok(None);
+  }
+  LANGUAGES.iter().find(|language| language.name == name)
+    .map(Some)
+    .ok_or_else(|| Error::InvalidArgument(format!("Unknown mnemonic language {}", name)))
+}
+
+fn entropy_size(words: usize) -> Result<usize> {
+  match words {
+    12 | 15 | 18 | 21 | 24 => Ok(words / 3 * 4),
+    _ => Err(Error::MnemonicWordCount { words: words })
+  }
+}
+
+fn checksum(entropy: &[u8]) -> u8 {
+  let mut hash = SecureBuffer::new(32);
+  let mut hasher = Sha256::new();
+  hasher.input(entropy);
+  hasher.result(&mut hash);
+  hash[0]
+}
+
+fn normalize(phrase: &str) -> SecureString {
+  SecureString::from(phrase.nfkd().collect::<String>())
+}
+
+fn decode_phrase(phrase: &str, language: &Language) -> Result<SecureBuffer> {
+  let words = phrase.split_whitespace().count();
+  let size = entropy_size(words)?;
+
+  // entropy followed by the checksum bits
+  let mut bits = SecureBuffer::new(size + 1);
+  for (position, word) in phrase.split_whitespace().enumerate() {
+    let index = language.lookup(word).ok_or(Error::MnemonicUnknownWord { position: position })?;
+    for bit in 0..WORD_BITS {
+      if index & (1 << (WORD_BITS - 1 - bit)) != 0 {
+        let offset = position * WORD_BITS + bit;
+        bits[offset / 8] |= 1 << (7 - offset % 8);
+      }
+    }
+  }
+
+  let checksum_bits = words / 3;
+  if checksum(&bits[..size]) >> (8 - checksum_bits) != bits[size] >> (8 - checksum_bits) {
+    return Err(Error::MnemonicChecksum);
+  }
+
+  let mut entropy = SecureBuffer::new(size);
+  entropy.copy_from_slice(&bits[..size]);
+  Ok(entropy)
+}
+
+fn first_unknown_word(phrase: &str, language: &Language) -> Option<usize> {
+  phrase.split_whitespace().position(|word| language.lookup(word).is_none())
+}
+
+// Languages containing every word of the phrase. When there is none the error
+// points at the first unknown word of the language matching the longest run of words.
+fn detect(phrase: &str) -> Result<Vec<&'static Language>> {
+  let mut candidates = Vec::new();
+  let mut best_position = 0;
+  for language in LANGUAGES.iter() {
+    match first_unknown_word(phrase, language) {
+      None => candidates.push(language),
+      Some(position) => if position > best_position { best_position = position }
+    }
+  }
+  if candidates.is_empty() {
+    return Err(Error::MnemonicUnknownWord { position: best_position });
+  }
+  Ok(candidates)
+}
+
+// Chinese dictionaries share a lot of words, the checksum picks the right one
+fn detect_and_decode(phrase: &str) -> Result<(&'static Language, SecureBuffer)> {
+  let mut error = Error::MnemonicChecksum;
+  for language in detect(phrase)? {
+    match decode_phrase(phrase, language) {
+      Ok(entropy) => return Ok((language, entropy)),
+      Err(err) => error = err
+    }
+  }
+  Err(error)
+}
+
+fn decode_with_language(phrase: &str, language: &str) -> Result<(&'static Language, SecureBuffer)> {
+  let normalized = normalize(phrase);
+  match find_language(language)? {
+    Some(language) => decode_phrase(&normalized, language).map(|entropy| (language, entropy)),
+    None => detect_and_decode(&normalized)
+  }
+}
+
+// Entropy of a phrase in `language`, detected when empty
+pub fn phrase_to_entropy(phrase: &str, language: &str) -> Result<SecureBuffer> {
+  decode_with_language(phrase, language).map(|(_, entropy)| entropy)
+}
+
+pub fn entropy_to_phrase(entropy: &[u8], language: &str) -> Result<SecureString> {
+  let language = find_language(language)?.unwrap_or(&LANGUAGES[0]);
+  let words = entropy.len() / 4 * 3;
+  if entropy.len() % 4 != 0 || entropy_size(words).is_err() {
+    return Err(Error::InvalidArgument(format!("Wrong entropy len {} should be 16, 20, 24, 28 or 32", entropy.len())));
+  }
+
+  let mut bits = SecureBuffer::new(entropy.len() + 1);
+  bits[..entropy.len()].copy_from_slice(entropy);
+  bits[entropy.len()] = checksum(entropy);
+
+  // Reserved upfront so the phrase is never reallocated and copied around
+  let mut phrase = String::with_capacity(words * (language.max_word_len + language.separator.len()));
+  for position in 0..words {
+    let mut index: usize = 0;
+    for bit in 0..WORD_BITS {
+      let offset = position * WORD_BITS + bit;
+      index = (index << 1) | ((bits[offset / 8] >> (7 - offset % 8)) & 1) as usize;
+    }
+    if position > 0 { phrase.push_str(language.separator); }
+    phrase.push_str(language.words[index]);
+  }
+  Ok(SecureString::from(phrase))
+}
+
+// Params: words: Number, language: String
+pub fn generate(mut cx: FunctionContext) -> JsResult<JsString> {
+  let words = cx.argument::<JsNumber>(0)?.value() as u32 as usize;
+  let language = cx.argument::<JsString>(1)?.value();
+
+  entropy_size(words).and_then(|size| {
+    let mut entropy = SecureBuffer::new(size);
+    OsRng::new()
+      .map_err(|err| Error::Native(format!("Can't access secure randomness: {}", err)))?
+      .fill_bytes(&mut entropy);
+    entropy_to_phrase(&entropy, &language)
+  }).and_then(|phrase| {
+    cx.try_string(&*phrase).map_err(|_| Error::Native(String::from("Can't create JS string")))
+  }).or_throw(&mut cx)
+}
+
+// Params: phrase: String, language: String
+// Returns: language of the phrase
+pub fn validate(mut cx: FunctionContext) -> JsResult<JsString> {
+  let phrase = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let language = cx.argument::<JsString>(1)?.value();
+
+  let (language, _) = decode_with_language(&phrase, &language).or_throw(&mut cx)?;
+  Ok(cx.string(language.name))
+}
+
+// Params: phrase: String, language: String
+pub fn to_entropy(mut cx: FunctionContext) -> JsResult<JsBuffer> {
+  let phrase = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let language = cx.argument::<JsString>(1)?.value();
+
+  let entropy = phrase_to_entropy(&phrase, &language).or_throw(&mut cx)?;
+  let mut output_buf = cx.buffer(entropy.len() as u32)?;
+  {
+    let guard = cx.lock();
+    let mut output = output_buf.borrow_mut(&guard);
+    output.as_mut_slice::<u8>().copy_from_slice(&entropy);
+  }
+  Ok(output_buf)
+}
+
+// Params: entropy: Buffer, language: String
+pub fn from_entropy(mut cx: FunctionContext) -> JsResult<JsString> {
+  let entropy = cx.argument::<JsBuffer>(0)?;
+  let language = cx.argument::<JsString>(1)?.value();
+
+  {
+    let guard = cx.lock();
+    let entropy_buf = entropy.borrow(&guard);
+    entropy_to_phrase(entropy_buf.as_slice::<u8>(), &language)
+  }.and_then(|phrase| {
+    cx.try_string(&*phrase).map_err(|_| Error::Native(String::from("Can't create JS string")))
+  }).or_throw(&mut cx)
+}
+
+// Params: prefix: String, language: String
+// Returns: dictionary words starting with the prefix
+pub fn suggest(mut cx: FunctionContext) -> JsResult<JsArray> {
+  let prefix: String = cx.argument::<JsString>(0)?.value().nfkd().collect();
+  let language = cx.argument::<JsString>(1)?.value();
+
+  let language = find_language(&language).or_throw(&mut cx)?.unwrap_or(&LANGUAGES[0]);
+  let words: Vec<&'static str> = language.normalized.iter().enumerate()
+    .filter(|&(_, word)| word.starts_with(prefix.as_str()))
+    .map(|(index, _)| language.words[index])
+    .collect();
+
+  let array = JsArray::new(&mut cx, words.len() as u32);
+  for (index, word) in words.iter().enumerate() {
+    let word = cx.string(word);
+    array.set(&mut cx, index as u32, word)?;
+  }
+  Ok(array)
+}
+
+// Params: phrase: String
+// Returns: name of the first language containing every word of the phrase
+pub fn detect_language(mut cx: FunctionContext) -> JsResult<JsString> {
+  let phrase = SecureString::from(cx.argument::<JsString>(0)?.value());
+
+  let language = detect(&normalize(&phrase)).map(|candidates| candidates[0]).or_throw(&mut cx)?;
+  Ok(cx.string(language.name))
+}
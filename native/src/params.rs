@@ -0,0 +1,41 @@
+// Linear fee, size limit and deposits of a network. Requests may carry their own
+// `protocol_params`, otherwise the mainnet values of the transaction era are used.
+#[derive(Clone, Copy, Deserialize)]
+pub struct ProtocolParams {
+  pub fee_constant: u64,
+  // Lovelace per byte, fractional for Byron (43.946 on mainnet)
+  pub fee_coefficient: f64,
+  pub max_tx_size: usize,
+  pub min_utxo: u64,
+  pub key_deposit: u64,
+  pub pool_deposit: u64
+}
+
+impl ProtocolParams {
+  pub fn byron() -> Self {
+    ProtocolParams {
+      fee_constant: 155381,
+      fee_coefficient: 43.946,
+      max_tx_size: 65536,
+      min_utxo: 0,
+      key_deposit: 0,
+      pool_deposit: 0
+    }
+  }
+
+  pub fn shelley() -> Self {
+    ProtocolParams {
+      fee_constant: 155381,
+      fee_coefficient: 44.0,
+      max_tx_size: 16384,
+      min_utxo: 1000000,
+      key_deposit: 2000000,
+      pool_deposit: 500000000
+    }
+  }
+
+  // `constant + coefficient * size`, rounded up to the next lovelace
+  pub fn min_fee(&self, size: usize) -> u64 {
+    self.fee_constant + (self.fee_coefficient * size as f64).ceil() as u64
+  }
+}
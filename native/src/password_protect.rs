@@ -22,11 +22,11 @@ pub fn encrypt_with_password(mut cx: FunctionContext) -> JsResult<JsBuffer> {
     let bpwd = pwd.borrow(&guard);
 
     if bpwd.len() <= 0 {
-      Err(String::from("Password can't be empty"))
+      Err(Error::InvalidArgument(String::from("Password can't be empty")))
     } else if bsalt.len() != SALT_SIZE {
-      Err(format!("Wrong salt len {} should be {}", bsalt.len(), SALT_SIZE))
+      Err(Error::InvalidLength { field: "salt", actual: bsalt.len(), expected: SALT_SIZE })
     } else if bnonce.len() != NONCE_SIZE {
-      Err(format!("Wrong nonce len {} should be {}", bnonce.len(), NONCE_SIZE))
+      Err(Error::InvalidLength { field: "nonce", actual: bnonce.len(), expected: NONCE_SIZE })
     } else {
       Ok(bdata.len() + TAG_SIZE + NONCE_SIZE + SALT_SIZE)
     }
@@ -44,13 +44,13 @@ pub fn encrypt_with_password(mut cx: FunctionContext) -> JsResult<JsBuffer> {
       let output: MutBufferPtr = js_buffer.borrow_mut(&guard).into();
 
       handle_exception(|| {
-        wallet_wasm::encrypt_with_password(
+        Ok(wallet_wasm::encrypt_with_password(
           bpwd.ptr, bpwd.size, bsalt.ptr, bnonce.ptr, bdata.ptr, bdata.size,
           output.ptr
-        ) as usize
+        ) as usize)
       }).and_then(|rsz| {
         if rsz != output.size {
-          return Err(format!("Size mismatch {} should be {}", rsz, output.size));
+          return Err(Error::Native(format!("Size mismatch {} should be {}", rsz, output.size)));
         }
         Ok(js_buffer)
       })
@@ -68,9 +68,9 @@ pub fn decrypt_with_password(mut cx: FunctionContext) -> JsResult<JsBuffer> {
     let bpwd = pwd.borrow(&guard);
 
     if bdata.len() <= TAG_SIZE + NONCE_SIZE + SALT_SIZE { 
-      Err(format!("Wrong data len {} should be at least {}", bdata.len(), TAG_SIZE + NONCE_SIZE + SALT_SIZE + 1))
+      Err(Error::TooShort { field: "data", actual: bdata.len(), min: TAG_SIZE + NONCE_SIZE + SALT_SIZE + 1 })
     } else if bpwd.len() <= 0 {
-      Err(String::from("Password can't be empty"))
+      Err(Error::InvalidArgument(String::from("Password can't be empty")))
     } else {
       Ok(bdata.len() - TAG_SIZE - NONCE_SIZE - SALT_SIZE)
     }
@@ -86,18 +86,22 @@ pub fn decrypt_with_password(mut cx: FunctionContext) -> JsResult<JsBuffer> {
       let output: MutBufferPtr = js_buffer.borrow_mut(&guard).into();
 
       handle_exception(|| {
-        wallet_wasm::decrypt_with_password(
+        Ok(wallet_wasm::decrypt_with_password(
           bpwd.ptr, bpwd.size, bdata.ptr, bdata.size,
           output.ptr
-        )
+        ))
       }).and_then(|rsz| {
         if rsz <= 0 {
-          return Err(String::from("Decryption failed. Check your password."));
+          return Err(Error::BadPassword);
         }
         if rsz as usize != output.size {
-          return Err(format!("Decrypted data size mismatch {} should be {}", rsz, output.size));
+          return Err(Error::Native(format!("Decrypted data size mismatch {} should be {}", rsz, output.size)));
         }
         Ok(js_buffer)
+      }).map_err(|err| {
+        // Don't leave partially decrypted data in the discarded buffer
+        secure_zero(output.as_sized_slice(output.size));
+        err
       })
     }.or_throw(&mut cx)
   })
@@ -0,0 +1,235 @@
+use neon::prelude::*;
+use exception::*;
+use buffer::*;
+use wallet_wasm;
+use cardano::hdwallet;
+use hdwallet::{derive_private_indices, parse_path};
+
+// Rust owned XPrv. The key bytes stay in native memory
+// and are wiped on destroy() or when the JS object is collected.
+pub struct PrivateKey {
+  xprv: Option<SecureBuffer>
+}
+
+impl PrivateKey {
+  fn xprv(&self) -> Result<&[u8]> {
+    self.xprv.as_ref().map(|xprv| &xprv[..]).ok_or(Error::KeyDestroyed)
+  }
+
+  // Dropping the buffer wipes the key
+  fn destroy(&mut self) {
+    self.xprv = None;
+  }
+}
+
+fn new_private_key<'a, C: Context<'a>>(cx: &mut C, xprv: SecureBuffer) -> JsResult<'a, JsPrivateKey> {
+  let args: Vec<Handle<JsValue>> = Vec::new();
+  let mut key = JsPrivateKey::new(cx, args)?;
+  {
+    let guard = cx.lock();
+    key.borrow_mut(&guard).xprv = Some(xprv);
+  }
+  Ok(key)
+}
+
+fn empty_xprv() -> SecureBuffer {
+  SecureBuffer::new(hdwallet::XPRV_SIZE)
+}
+
+declare_types! {
+  pub class JsPrivateKey for PrivateKey {
+    init(_cx) {
+      Ok(PrivateKey { xprv: None })
+    }
+
+    // Returns: XPub Buffer
+    method toPublic(mut cx) {
+      let this = cx.this();
+      let mut output_buf = cx.buffer(hdwallet::XPUB_SIZE as u32)?;
+      {
+        let guard = cx.lock();
+        let key = this.borrow(&guard);
+        let output: MutBufferPtr = output_buf.borrow_mut(&guard).into();
+
+        key.xprv().and_then(|xprv| {
+          handle_exception(|| {
+            wallet_wasm::wallet_to_public(xprv.as_ptr(), output.ptr);
+            Ok(())
+          })
+        })
+      }.or_throw(&mut cx)?;
+      Ok(output_buf.upcast())
+    }
+
+    // Params: index: Number
+    // Returns: PrivateKey
+    method derive(mut cx) {
+      let index = cx.argument::<JsNumber>(0)?.value() as u32;
+      let this = cx.this();
+      let derived = {
+        let guard = cx.lock();
+        let key = this.borrow(&guard);
+
+        key.xprv().and_then(|xprv| {
+          let mut output = empty_xprv();
+          let output_ptr = output.as_mut_ptr();
+          handle_exception(|| {
+            wallet_wasm::wallet_derive_private(xprv.as_ptr(), index, output_ptr);
+            Ok(())
+          }).map(|_| output)
+        })
+      }.or_throw(&mut cx)?;
+      Ok(new_private_key(&mut cx, derived)?.upcast())
+    }
+
+    // Params: path: String
+    // Returns: PrivateKey
+    method derivePath(mut cx) {
+      let path = cx.argument::<JsString>(0)?.value();
+      let this = cx.this();
+      let derived = {
+        let guard = cx.lock();
+        let key = this.borrow(&guard);
+
+        key.xprv().and_then(|xprv| {
+          let indices = parse_path(&path)?;
+          let mut output = empty_xprv();
+          let output_ptr = output.as_mut_ptr();
+          handle_exception(|| {
+            derive_private_indices(xprv.as_ptr(), &indices, output_ptr);
+            Ok(())
+          }).map(|_| output)
+        })
+      }.or_throw(&mut cx)?;
+      Ok(new_private_key(&mut cx, derived)?.upcast())
+    }
+
+    // Params: data: Buffer
+    // Returns: Signature Buffer
+    method sign(mut cx) {
+      let data = cx.argument::<JsBuffer>(0)?;
+      let this = cx.this();
+      let mut output_buf = cx.buffer(hdwallet::SIGNATURE_SIZE as u32)?;
+      {
+        let guard = cx.lock();
+        let key = this.borrow(&guard);
+        let data_buf: BufferPtr = data.borrow(&guard).into();
+        let output: MutBufferPtr = output_buf.borrow_mut(&guard).into();
+
+        key.xprv().and_then(|xprv| {
+          handle_exception(|| {
+            wallet_wasm::wallet_sign(xprv.as_ptr(), data_buf.ptr, data_buf.size, output.ptr);
+            Ok(())
+          })
+        })
+      }.or_throw(&mut cx)?;
+      Ok(output_buf.upcast())
+    }
+
+    // Copies the key bytes into a JS Buffer.
+    // Returns: XPrv Buffer
+    method export(mut cx) {
+      let this = cx.this();
+      let mut output_buf = cx.buffer(hdwallet::XPRV_SIZE as u32)?;
+      {
+        let guard = cx.lock();
+        let key = this.borrow(&guard);
+        let mut output = output_buf.borrow_mut(&guard);
+
+        key.xprv().map(|xprv| {
+          output.as_mut_slice::<u8>().copy_from_slice(&xprv[..]);
+        })
+      }.or_throw(&mut cx)?;
+      Ok(output_buf.upcast())
+    }
+
+    method isDestroyed(mut cx) {
+      let this = cx.this();
+      let destroyed = {
+        let guard = cx.lock();
+        let key = this.borrow(&guard);
+        key.xprv.is_none()
+      };
+      Ok(cx.boolean(destroyed).upcast())
+    }
+
+    method destroy(mut cx) {
+      let mut this = cx.this();
+      {
+        let guard = cx.lock();
+        this.borrow_mut(&guard).destroy();
+      }
+      Ok(cx.undefined().upcast())
+    }
+  }
+}
+
+// Params: entropy: Buffer, password: Buffer
+pub fn from_enhanced_entropy(mut cx: FunctionContext) -> JsResult<JsPrivateKey> {
+  let entropy = cx.argument::<JsBuffer>(0)?;
+  let pwd = cx.argument::<JsBuffer>(1)?;
+
+  let xprv = {
+    let guard = cx.lock();
+    let entropy_buf: BufferPtr = entropy.borrow(&guard).into();
+    let pwd_buf: BufferPtr = pwd.borrow(&guard).into();
+    let mut output = empty_xprv();
+    let output_ptr = output.as_mut_ptr();
+
+    handle_exception(|| {
+      Ok(wallet_wasm::wallet_from_enhanced_entropy(
+        entropy_buf.ptr, entropy_buf.size, pwd_buf.ptr, pwd_buf.size, output_ptr
+      ))
+    }).and_then(|res| {
+      if res != 0 {
+        return Err(Error::InvalidArgument(format!("Rust method error. Check entropy size {}.", entropy_buf.size)));
+      }
+      Ok(output)
+    })
+  }.or_throw(&mut cx)?;
+
+  new_private_key(&mut cx, xprv)
+}
+
+// Params: seed: Buffer
+pub fn from_seed(mut cx: FunctionContext) -> JsResult<JsPrivateKey> {
+  let seed = cx.argument::<JsBuffer>(0)?;
+
+  let xprv = {
+    let guard = cx.lock();
+    let seed_buf: BufferPtr = seed.borrow(&guard).into();
+    let mut output = empty_xprv();
+    let output_ptr = output.as_mut_ptr();
+
+    if seed_buf.size != hdwallet::SEED_SIZE {
+      Err(Error::InvalidLength { field: "seed", actual: seed_buf.size, expected: hdwallet::SEED_SIZE })
+    } else {
+      handle_exception(|| {
+        wallet_wasm::wallet_from_seed(seed_buf.ptr, output_ptr);
+        Ok(())
+      }).map(|_| output)
+    }
+  }.or_throw(&mut cx)?;
+
+  new_private_key(&mut cx, xprv)
+}
+
+// Params: xprv: Buffer
+pub fn from_xprv(mut cx: FunctionContext) -> JsResult<JsPrivateKey> {
+  let xprv = cx.argument::<JsBuffer>(0)?;
+
+  let key = {
+    let guard = cx.lock();
+    let xprv_buf = xprv.borrow(&guard);
+
+    if xprv_buf.len() != hdwallet::XPRV_SIZE {
+      Err(Error::InvalidLength { field: "XPrv", actual: xprv_buf.len(), expected: hdwallet::XPRV_SIZE })
+    } else {
+      let mut output = empty_xprv();
+      output.copy_from_slice(xprv_buf.as_slice::<u8>());
+      Ok(output)
+    }
+  }.or_throw(&mut cx)?;
+
+  new_private_key(&mut cx, key)
+}
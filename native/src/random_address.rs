@@ -0,0 +1,64 @@
+use neon::prelude::*;
+use exception::*;
+use json::*;
+use address::network_magic;
+use hdwallet::HARDENED_INDEX;
+use byron_tx::{derive_keys, ByronWallet};
+use random_checker::addressing_array;
+use cardano::address::{AddrType, Attributes, ExtendedAddr, SpendingData};
+use cardano::hdpayload::{HDKey, Path};
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+#[derive(Deserialize)]
+struct GenerateRequest {
+  wallet: ByronWallet,
+  // Hardened `[account, index]`, random when missing
+  #[serde(default)]
+  addressing: Option<[u32; 2]>
+}
+
+fn random_addressing() -> Result<[u32; 2]> {
+  let mut rng = OsRng::new()
+    .map_err(|err| Error::Native(format!("Can't access secure randomness: {}", err)))?;
+  Ok([HARDENED_INDEX | rng.next_u32(), HARDENED_INDEX | rng.next_u32()])
+}
+
+// Daedalus address of the key at `addressing`. The path is encrypted with
+// ChaCha20-Poly1305 under a key derived from the root XPub, so that only the
+// wallet can tell its addresses apart from the others.
+fn generate(wallet: &ByronWallet, addressing: [u32; 2]) -> Result<String> {
+  if addressing.iter().any(|index| *index < HARDENED_INDEX) {
+    return Err(Error::InvalidArgument(format!(
+      "Wrong addressing {:?}, random addresses use hardened indices", addressing
+    )));
+  }
+  let keys = derive_keys(wallet, &[Vec::new(), addressing.to_vec()])?;
+  let payload = HDKey::new(&keys[0].public()).encrypt_path(&Path::new(addressing.to_vec()));
+  let attributes = Attributes::new_bootstrap_era(Some(payload), network_magic(wallet.config.protocol_magic));
+  let address = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(keys[1].public()), attributes);
+  Ok(format!("{}", address))
+}
+
+// Params: params: JSONString { wallet, addressing? }
+// Returns: { address: String, addressing: [Number, Number] }
+pub fn generate_address(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+
+  let (address, addressing) = parse_request::<GenerateRequest>(&params).and_then(|request| {
+    handle_exception(|| {
+      let addressing = match request.addressing {
+        Some(addressing) => addressing,
+        None => random_addressing()?
+      };
+      Ok((generate(&request.wallet, addressing)?, addressing))
+    })
+  }).or_throw(&mut cx)?;
+
+  let object = cx.empty_object();
+  let address = cx.string(address);
+  object.set(&mut cx, "address", address)?;
+  let addressing = addressing_array(&mut cx, addressing)?;
+  object.set(&mut cx, "addressing", addressing)?;
+  Ok(object)
+}
@@ -2,79 +2,329 @@ use neon::prelude::*;
 use exception::*;
 use buffer::*;
 use wallet_wasm;
-use std::str;
-use std::cmp;
+use task::JsonTask;
+use json::*;
+use address::{decode_base58, decode_byron};
+use mnemonic::phrase_to_entropy;
+use cbor;
+use cardano::hdwallet::{self, XPrv};
+use cardano::hdpayload::{HDAddressPayload, HDKey};
+use cryptoxide::blake2b::Blake2b;
+use cryptoxide::digest::Digest;
+use std::sync::Arc;
 use super::MAX_OUTPUT_SIZE;
 
-// Params: xprv: "HexString"
-pub fn new_checker(mut cx: FunctionContext) -> JsResult<JsString> {
-  let xprv_str = cx.argument::<JsString>(0)?.value();
-  let mut output = [0 as u8; MAX_OUTPUT_SIZE];
-  let buf = MutBufferPtr::from(&mut output);
-  
-  handle_exception(|| {
-    let xprv: &[u8] = xprv_str.as_bytes();
-    
-    let rsz = wallet_wasm::random_address_checker_new(xprv.as_ptr(), xprv.len(), buf.ptr);
-
-    if rsz <= 0 { panic!("Response {} <= 0", rsz); }
-    if (rsz as usize) > MAX_OUTPUT_SIZE { panic!("Response {} >= {}", rsz, MAX_OUTPUT_SIZE) }
-
-    buf.as_sized_slice(rsz as usize)
-  }).map(|output| {
-    unsafe { str::from_utf8_unchecked(output) }
-  })
-  .and_then(|string| {
-    cx.try_string(string).map_err(|_| String::from("Can't create JS string"))
-  }).or_throw(&mut cx)
-}
-
-// Params: mnemonics: "String"
-pub fn new_checker_from_mnemonics(mut cx: FunctionContext) -> JsResult<JsString> {
-  let mnemonics_str = cx.argument::<JsString>(0)?.value();
-  let mut output = [0 as u8; MAX_OUTPUT_SIZE];
-  let buf = MutBufferPtr::from(&mut output);
-  
-  handle_exception(|| {
-    let mnemonics: &[u8] = mnemonics_str.as_bytes();
-    
-    let rsz = wallet_wasm::random_address_checker_from_mnemonics(mnemonics.as_ptr(), mnemonics.len(), buf.ptr);
-
-    if rsz <= 0 { panic!("Response {} <= 0", rsz); }
-    if (rsz as usize) > MAX_OUTPUT_SIZE { panic!("Response {} >= {}", rsz, MAX_OUTPUT_SIZE) }
-
-    buf.as_sized_slice(rsz as usize)
-  }).map(|output| {
-    unsafe { str::from_utf8_unchecked(output) }
-  })
-  .and_then(|string| {
-    cx.try_string(string).map_err(|_| String::from("Can't create JS string"))
-  }).or_throw(&mut cx)
-}
-
-// Params: params: JSONString, alen: Number
-pub fn check_addresses(mut cx: FunctionContext) -> JsResult<JsString> {
-  let params_str = cx.argument::<JsString>(0)?.value();
-  
-  let output_size = cmp::max(params_str.len(), MAX_OUTPUT_SIZE);
-  let mut output: Vec<u8> = Vec::new();
-  output.resize(output_size, 0);
-
-  let buf = MutBufferPtr::from(&mut output);
-  
-  handle_exception(|| {
-    let params: &[u8] = params_str.as_bytes();
-    
-    let rsz = wallet_wasm::random_address_check(params.as_ptr(), params.len(), buf.ptr);
-
-    if rsz <= 0 { panic!("Response {} <= 0", rsz); }
-    if (rsz as usize) > output_size { panic!("Response {} >= {}", rsz, output_size) }
-
-    buf.as_sized_slice(rsz as usize)
-  }).map(|output| {
-    unsafe { str::from_utf8_unchecked(output) }
-  })
-  .and_then(|string| {
-    cx.try_string(string).map_err(|_| String::from("Can't create JS string"))
-  }).or_throw(&mut cx)
-}
\ No newline at end of file
+const DAEDALUS_SEED_SIZE: usize = 32;
+// JSON size of one found address besides the address itself:
+// {"address":"","addressing":[,]}, is 32 chars and the two u32 indices take at most 20
+const FOUND_ADDRESS_JSON_SIZE: usize = 32 + 20;
+
+// Rust owned HD payload key. The key stays in native memory and is wiped
+// on destroy() or once the JS object and the pending checks are collected.
+pub struct RandomAddressChecker {
+  key: Option<Arc<HDKey>>
+}
+
+impl RandomAddressChecker {
+  fn key(&self) -> Result<&Arc<HDKey>> {
+    self.key.as_ref().ok_or(Error::KeyDestroyed)
+  }
+
+  fn destroy(&mut self) {
+    // HDKey wipes itself when dropped
+    self.key.take();
+  }
+}
+
+// Address passed from JS, a Buffer or a base58 string
+enum AddressArgument {
+  Buffer(Vec<u8>),
+  Base58(String, Option<Vec<u8>>)
+}
+
+impl AddressArgument {
+  fn bytes(&self) -> Option<&[u8]> {
+    match *self {
+      AddressArgument::Buffer(ref bytes) => Some(bytes),
+      AddressArgument::Base58(_, ref bytes) => bytes.as_ref().map(|bytes| &bytes[..])
+    }
+  }
+
+  fn to_js<'a, C: Context<'a>>(&self, cx: &mut C) -> JsResult<'a, JsValue> {
+    match *self {
+      AddressArgument::Buffer(ref bytes) => {
+        let mut buffer = cx.buffer(bytes.len() as u32)?;
+        {
+          let guard = cx.lock();
+          buffer.borrow_mut(&guard).as_mut_slice::<u8>().copy_from_slice(bytes);
+        }
+        Ok(buffer.upcast())
+      },
+      AddressArgument::Base58(ref address, _) => Ok(cx.string(address).upcast())
+    }
+  }
+}
+
+// `[account, index]` of the wallet addresses. Addresses of other wallets,
+// without HD payload or which can't be decoded belong to someone else.
+fn check_address(key: &HDKey, address: Option<&[u8]>) -> Option<[u32; 2]> {
+  let decoded = decode_byron(address?).ok()?;
+  let path = key.decrypt_path(&HDAddressPayload::from_bytes(&decoded.hd_payload?)).ok()?;
+  match path.as_ref() {
+    &[account, index] => Some([account, index]),
+    _ => None
+  }
+}
+
+// Positions of the wallet addresses with their addressing
+fn check_addresses(key: &HDKey, addresses: &[AddressArgument]) -> Vec<(usize, [u32; 2])> {
+  addresses.iter().enumerate()
+    .filter_map(|(position, address)| check_address(key, address.bytes()).map(|addressing| (position, addressing)))
+    .collect()
+}
+
+// Daedalus root: the seed is the CBOR bytes of blake2b-256 of the CBOR bytes of the entropy.
+// The mnemonic language is detected, Daedalus itself only used English.
+fn daedalus_root_xpub(mnemonics: &str) -> Result<Vec<u8>> {
+  let entropy = phrase_to_entropy(mnemonics, "")?;
+  let mut entropy_cbor = cbor::Writer::new();
+  entropy_cbor.bytes(&entropy);
+  let mut entropy_cbor = entropy_cbor.into_bytes();
+  let mut hash = [0 as u8; DAEDALUS_SEED_SIZE];
+  let mut hasher = Blake2b::new(DAEDALUS_SEED_SIZE);
+  hasher.input(&entropy_cbor);
+  hasher.result(&mut hash);
+  secure_zero(&mut entropy_cbor);
+
+  let mut seed = cbor::Writer::new();
+  seed.bytes(&hash);
+  let mut seed = seed.into_bytes();
+  secure_zero(&mut hash);
+  let xprv = XPrv::generate_from_daedalus_seed(&seed);
+  secure_zero(&mut seed);
+  Ok(xprv.public().as_ref().to_vec())
+}
+
+fn new_checker_object<'a, C: Context<'a>>(cx: &mut C, key: HDKey) -> JsResult<'a, JsRandomAddressChecker> {
+  let args: Vec<Handle<JsValue>> = Vec::new();
+  let mut checker = JsRandomAddressChecker::new(cx, args)?;
+  {
+    let guard = cx.lock();
+    checker.borrow_mut(&guard).key = Some(Arc::new(key));
+  }
+  Ok(checker)
+}
+
+fn address_argument<'a, C: Context<'a>>(cx: &mut C, value: Handle<'a, JsValue>) -> NeonResult<AddressArgument> {
+  if let Ok(buffer) = value.downcast::<JsBuffer>() {
+    let guard = cx.lock();
+    let bytes = buffer.borrow(&guard).as_slice::<u8>().to_vec();
+    return Ok(AddressArgument::Buffer(bytes));
+  }
+  let address = value.downcast_or_throw::<JsString, _>(cx)?.value();
+  let bytes = decode_base58(&address).ok();
+  Ok(AddressArgument::Base58(address, bytes))
+}
+
+fn address_arguments<'a, C: Context<'a>>(cx: &mut C, values: &[Handle<'a, JsValue>]) -> NeonResult<Vec<AddressArgument>> {
+  let mut addresses = Vec::with_capacity(values.len());
+  for value in values.iter() {
+    addresses.push(address_argument(cx, *value)?);
+  }
+  Ok(addresses)
+}
+
+pub(crate) fn addressing_array<'a, C: Context<'a>>(cx: &mut C, addressing: [u32; 2]) -> JsResult<'a, JsArray> {
+  let array = JsArray::new(cx, 2);
+  for (position, index) in addressing.iter().enumerate() {
+    let index = cx.number(*index as f64);
+    array.set(cx, position as u32, index)?;
+  }
+  Ok(array)
+}
+
+// `[{ address, addressing }]` of the found addresses, `address` being given by `address_at`
+fn found_to_js<'a, C: Context<'a>, F>(cx: &mut C, found: &[(usize, [u32; 2])], address_at: F) -> JsResult<'a, JsArray>
+  where F: Fn(&mut C, usize) -> JsResult<'a, JsValue>
+{
+  let array = JsArray::new(cx, found.len() as u32);
+  for (index, &(position, addressing)) in found.iter().enumerate() {
+    let object = cx.empty_object();
+    let address = address_at(cx, position)?;
+    object.set(cx, "address", address)?;
+    let addressing = addressing_array(cx, addressing)?;
+    object.set(cx, "addressing", addressing)?;
+    array.set(cx, index as u32, object)?;
+  }
+  Ok(array)
+}
+
+// Checks addresses on the libuv thread pool. The task shares the key of the
+// checker, which is only wiped once both are gone.
+struct CheckTask {
+  key: Arc<HDKey>,
+  addresses: Vec<AddressArgument>
+}
+
+impl Task for CheckTask {
+  type Output = Vec<(usize, [u32; 2])>;
+  type Error = Error;
+  type JsEvent = JsArray;
+
+  fn perform(&self) -> Result<Vec<(usize, [u32; 2])>> {
+    Ok(check_addresses(&self.key, &self.addresses))
+  }
+
+  fn complete(self, mut cx: TaskContext, result: Result<Vec<(usize, [u32; 2])>>) -> JsResult<JsArray> {
+    let found = result.or_throw(&mut cx)?;
+    let addresses = &self.addresses;
+    found_to_js(&mut cx, &found, |cx, position| addresses[position].to_js(cx))
+  }
+}
+
+declare_types! {
+  pub class JsRandomAddressChecker for RandomAddressChecker {
+    init(_cx) {
+      Ok(RandomAddressChecker { key: None })
+    }
+
+    // Params: addresses: Array<Buffer | String>
+    // Returns: Array<{ address: Buffer | String, addressing: [Number, Number] }> of the wallet addresses
+    method check(mut cx) {
+      let values = cx.argument::<JsArray>(0)?.to_vec(&mut cx)?;
+      let addresses = address_arguments(&mut cx, &values)?;
+      let this = cx.this();
+      let key = {
+        let guard = cx.lock();
+        let checker = this.borrow(&guard);
+        checker.key().map(|key| key.clone())
+      }.or_throw(&mut cx)?;
+
+      let found = check_addresses(&key, &addresses);
+      Ok(found_to_js(&mut cx, &found, |_, position| Ok(values[position]))?.upcast())
+    }
+
+    // Params: address: Buffer | String
+    // Returns: [Number, Number] addressing, null for addresses of other wallets
+    method checkOne(mut cx) {
+      let value = cx.argument::<JsValue>(0)?;
+      let address = address_argument(&mut cx, value)?;
+      let this = cx.this();
+      let addressing = {
+        let guard = cx.lock();
+        let checker = this.borrow(&guard);
+        checker.key().map(|key| check_address(key, address.bytes()))
+      }.or_throw(&mut cx)?;
+      match addressing {
+        Some(addressing) => Ok(addressing_array(&mut cx, addressing)?.upcast()),
+        None => Ok(cx.null().upcast())
+      }
+    }
+
+    // Params: addresses: Array<Buffer | String>, callback: Function
+    method checkAsync(mut cx) {
+      let values = cx.argument::<JsArray>(0)?.to_vec(&mut cx)?;
+      let callback = cx.argument::<JsFunction>(1)?;
+      let addresses = address_arguments(&mut cx, &values)?;
+      let this = cx.this();
+      let key = {
+        let guard = cx.lock();
+        let checker = this.borrow(&guard);
+        checker.key().map(|key| key.clone())
+      }.or_throw(&mut cx)?;
+
+      CheckTask { key: key, addresses: addresses }.schedule(callback);
+      Ok(cx.undefined().upcast())
+    }
+
+    method isDestroyed(mut cx) {
+      let this = cx.this();
+      let destroyed = {
+        let guard = cx.lock();
+        let checker = this.borrow(&guard);
+        checker.key.is_none()
+      };
+      Ok(cx.boolean(destroyed).upcast())
+    }
+
+    method destroy(mut cx) {
+      let mut this = cx.this();
+      {
+        let guard = cx.lock();
+        this.borrow_mut(&guard).destroy();
+      }
+      Ok(cx.undefined().upcast())
+    }
+  }
+}
+
+// Params: xprv: Buffer, the Daedalus wallet root
+pub fn new_checker(mut cx: FunctionContext) -> JsResult<JsRandomAddressChecker> {
+  let xprv = cx.argument::<JsBuffer>(0)?;
+
+  let key = {
+    let guard = cx.lock();
+    let xprv_buf = xprv.borrow(&guard);
+
+    if xprv_buf.len() != hdwallet::XPRV_SIZE {
+      Err(Error::InvalidLength { field: "XPrv", actual: xprv_buf.len(), expected: hdwallet::XPRV_SIZE })
+    } else {
+      XPrv::from_slice_verified(xprv_buf.as_slice::<u8>())
+        .map(|root| HDKey::new(&root.public()))
+        .map_err(|err| Error::InvalidArgument(format!("Invalid root key: {:?}", err)))
+    }
+  }.or_throw(&mut cx)?;
+
+  new_checker_object(&mut cx, key)
+}
+
+// Params: mnemonics: String
+pub fn new_checker_from_mnemonics(mut cx: FunctionContext) -> JsResult<JsRandomAddressChecker> {
+  let mnemonics = SecureString::from(cx.argument::<JsString>(0)?.value());
+
+  let key = handle_exception(|| {
+    let xpub = daedalus_root_xpub(&mnemonics)?;
+    hdwallet::XPub::from_slice(&xpub)
+      .map(|xpub| HDKey::new(&xpub))
+      .map_err(|err| Error::Native(format!("Invalid root XPub: {:?}", err)))
+  }).or_throw(&mut cx)?;
+
+  new_checker_object(&mut cx, key)
+}
+
+#[derive(Deserialize)]
+struct CheckRequest {
+  addresses: Vec<String>
+}
+
+fn check_capacity(params: &str) -> Result<usize> {
+  let request: CheckRequest = parse_request(params)?;
+  let addresses: usize = request.addresses.iter()
+    .map(|address| address.len() + FOUND_ADDRESS_JSON_SIZE)
+    .sum();
+  Ok(addresses + MAX_OUTPUT_SIZE)
+}
+
+// Deprecated: checks with a JSON checker of earlier versions, which carries its key.
+// Params: params: JSONString
+pub fn check_addresses_json(mut cx: FunctionContext) -> JsResult<JsString> {
+  let params_str = SecureString::from(cx.argument::<JsString>(0)?.value());
+
+  check_capacity(&params_str)
+    .and_then(|capacity| call_json(wallet_wasm::random_address_check, &params_str, capacity))
+    .and_then(|response| {
+      cx.try_string(&*response).map_err(|_| Error::Native(String::from("Can't create JS string")))
+    }).or_throw(&mut cx)
+}
+
+// Deprecated: `check_addresses_json` on the libuv thread pool.
+// Params: params: JSONString, callback: Function
+pub fn check_addresses_json_async(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let params_str = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let callback = cx.argument::<JsFunction>(1)?;
+
+  let capacity = check_capacity(&params_str).or_throw(&mut cx)?;
+  JsonTask::new(wallet_wasm::random_address_check, params_str, capacity).schedule(callback);
+
+  Ok(cx.undefined())
+}
@@ -0,0 +1,187 @@
+use exception::*;
+use rand::{RngCore, SeedableRng};
+use rand::rngs::{OsRng, StdRng};
+use rand::seq::SliceRandom;
+
+// Branches explored by MinimizeChange before falling back to LargestFirst
+const MAX_BRANCHES: usize = 10000;
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+pub enum SelectionPolicy {
+  FirstMatchFirst,
+  LargestFirst,
+  OldestFirst,
+  RandomImprove,
+  MinimizeChange
+}
+
+impl Default for SelectionPolicy {
+  fn default() -> Self {
+    SelectionPolicy::FirstMatchFirst
+  }
+}
+
+pub struct Utxo {
+  pub value: u64,
+  // Slot of the block which created the output, unknown ones count as the newest
+  pub slot: Option<u64>
+}
+
+// Transaction balanced over a selection
+pub trait Balanced {
+  fn change_used(&self) -> bool;
+}
+
+// Seeded generators give reproducible selections
+pub fn rng(seed: Option<u64>) -> Result<StdRng> {
+  let mut bytes = [0 as u8; 32];
+  match seed {
+    Some(seed) => for (index, byte) in bytes.iter_mut().take(8).enumerate() {
+      *byte = (seed >> (8 * index)) as u8;
+    },
+    None => OsRng::new()
+      .map_err(|err| Error::Native(format!("Can't access secure randomness: {}", err)))?
+      .fill_bytes(&mut bytes)
+  }
+  Ok(StdRng::from_seed(bytes))
+}
+
+// Adds `candidates` to `selected`, in order, until `balance` stops missing funds
+fn extend<T, F>(selected: &mut Vec<usize>, candidates: &[usize], balance: &F) -> Result<T>
+  where F: Fn(&[usize]) -> Result<T>
+{
+  let mut candidates = candidates.iter();
+  if selected.is_empty() {
+    match candidates.next() {
+      Some(&position) => selected.push(position),
+      None => return Err(Error::InvalidArgument(String::from("Transaction has no inputs")))
+    }
+  }
+  loop {
+    match balance(selected) {
+      Err(Error::InsufficientFunds { available, needed }) => match candidates.next() {
+        Some(&position) => selected.push(position),
+        None => return Err(Error::InsufficientFunds { available: available, needed: needed })
+      },
+      result => return result
+    }
+  }
+}
+
+// Covers every output, largest first, with random inputs, then keeps adding random
+// inputs while they bring the amount selected for the output closer to twice its
+// value, without going over three times. Fees are paid by further random inputs.
+fn random_improve<T, F>(utxos: &[Utxo], outputs: &[u64], rng: &mut StdRng, balance: &F) -> Result<(Vec<usize>, T)>
+  where F: Fn(&[usize]) -> Result<T>
+{
+  let mut pool: Vec<usize> = (0..utxos.len()).collect();
+  pool.shuffle(rng);
+  let mut targets = outputs.to_vec();
+  targets.sort_by(|a, b| b.cmp(a));
+
+  let mut selected = Vec::new();
+  for target in targets {
+    let mut sum: u64 = 0;
+    while sum < target {
+      match pool.pop() {
+        Some(position) => {
+          sum = sum.saturating_add(utxos[position].value);
+          selected.push(position);
+        },
+        None => {
+          let available = utxos.iter().fold(0, |total: u64, utxo| total.saturating_add(utxo.value));
+          let needed = outputs.iter().fold(0, |total: u64, value| total.saturating_add(*value));
+          return Err(Error::InsufficientFunds { available: available, needed: needed });
+        }
+      }
+    }
+    let ideal = target.saturating_mul(2);
+    let distance = |amount: u64| if amount > ideal { amount - ideal } else { ideal - amount };
+    while let Some(&position) = pool.last() {
+      let next = sum.saturating_add(utxos[position].value);
+      if next > target.saturating_mul(3) || distance(next) >= distance(sum) {
+        break;
+      }
+      pool.pop();
+      selected.push(position);
+      sum = next;
+    }
+  }
+  pool.reverse();
+  let balanced = extend(&mut selected, &pool, balance)?;
+  Ok((selected, balanced))
+}
+
+// Depth first search, largest inputs first, of a selection paying the outputs and
+// the fee without change. Branches are cut once they create change or can't reach
+// the amount needed.
+fn branch_and_bound<T, F>(utxos: &[Utxo], balance: &F) -> Option<(Vec<usize>, T)>
+  where F: Fn(&[usize]) -> Result<T>, T: Balanced
+{
+  let mut order: Vec<usize> = (0..utxos.len()).collect();
+  order.sort_by(|a, b| utxos[*b].value.cmp(&utxos[*a].value));
+  // Value still available from each depth on
+  let mut remaining = vec![0 as u64; order.len() + 1];
+  for depth in (0..order.len()).rev() {
+    remaining[depth] = remaining[depth + 1].saturating_add(utxos[order[depth]].value);
+  }
+
+  let mut selected = Vec::new();
+  // Depths of the inputs in `selected`
+  let mut path: Vec<usize> = Vec::new();
+  let mut depth = 0;
+  let mut sum: u64 = 0;
+  for _ in 0..MAX_BRANCHES {
+    if depth < order.len() {
+      selected.push(order[depth]);
+      path.push(depth);
+      sum = sum.saturating_add(utxos[order[depth]].value);
+      match balance(&selected) {
+        Ok(balanced) => if !balanced.change_used() {
+          return Some((selected, balanced));
+        },
+        Err(Error::InsufficientFunds { needed, .. }) => if sum.saturating_add(remaining[depth + 1]) >= needed {
+          depth += 1;
+          continue;
+        },
+        Err(_) => return None
+      }
+    }
+    // Drop the last input and try the branch without it
+    match path.pop() {
+      Some(last) => {
+        selected.pop();
+        sum -= utxos[order[last]].value;
+        depth = last + 1;
+      },
+      None => return None
+    }
+  }
+  None
+}
+
+// Picks the inputs spent to pay `outputs`. `balance` builds the transaction over a
+// selection, failing with `InsufficientFunds` while the selection is too small.
+// Returns the positions of the selected inputs with the balanced transaction.
+pub fn select<T, F>(
+  policy: SelectionPolicy, utxos: &[Utxo], outputs: &[u64], rng: &mut StdRng, balance: F
+) -> Result<(Vec<usize>, T)>
+  where F: Fn(&[usize]) -> Result<T>, T: Balanced
+{
+  let mut order: Vec<usize> = (0..utxos.len()).collect();
+  match policy {
+    SelectionPolicy::FirstMatchFirst => {},
+    SelectionPolicy::LargestFirst => order.sort_by(|a, b| utxos[*b].value.cmp(&utxos[*a].value)),
+    SelectionPolicy::OldestFirst => order.sort_by_key(|position| utxos[*position].slot.unwrap_or(u64::max_value())),
+    SelectionPolicy::RandomImprove => return random_improve(utxos, outputs, rng, &balance),
+    SelectionPolicy::MinimizeChange => {
+      if let Some(found) = branch_and_bound(utxos, &balance) {
+        return Ok(found);
+      }
+      order.sort_by(|a, b| utxos[*b].value.cmp(&utxos[*a].value));
+    }
+  }
+  let mut selected = Vec::new();
+  let balanced = extend(&mut selected, &order, &balance)?;
+  Ok((selected, balanced))
+}
@@ -0,0 +1,479 @@
+use neon::prelude::*;
+use exception::*;
+use address::{u32_argument, MAINNET_PROTOCOL_MAGIC};
+use hdwallet::{derive_private_indices, derive_public_indices, HARDENED_INDEX, PUBLIC_KEY_SIZE};
+use buffer::*;
+use wallet_wasm;
+use bech32::{self, FromBase32, ToBase32};
+use cardano::hdwallet;
+use cryptoxide::blake2b::Blake2b;
+use cryptoxide::digest::Digest;
+
+pub const NETWORK_TESTNET: u8 = 0;
+pub const NETWORK_MAINNET: u8 = 1;
+
+pub const HASH_SIZE: usize = 28;
+
+// Header types of the first address byte, the low nibble holds the network id
+const TYPE_BASE: u8 = 0;
+const TYPE_POINTER: u8 = 4;
+const TYPE_ENTERPRISE: u8 = 6;
+const TYPE_BYRON: u8 = 8;
+const TYPE_REWARD: u8 = 14;
+
+// CIP-1852 accounts live under m/1852'/1815'/account'
+pub const PURPOSE_SHELLEY: u32 = 1852;
+pub const COIN_TYPE: u32 = 1815;
+// CIP-1852 chain (role) of the staking key of an account
+pub const CHAIN_STAKING: u32 = 2;
+
+pub fn network_id(protocol_magic: u32) -> u8 {
+  if protocol_magic == MAINNET_PROTOCOL_MAGIC { NETWORK_MAINNET } else { NETWORK_TESTNET }
+}
+
+// Network ids fill the low nibble of the address header
+pub fn check_network_id(network: u32) -> Result<u8> {
+  if network > 0x0f {
+    return Err(Error::InvalidArgument(format!("Wrong network id {} should be at most 15", network)));
+  }
+  Ok(network as u8)
+}
+
+fn network_argument(value: f64) -> Result<u8> {
+  check_network_id(u32_argument(value, "network id")?)
+}
+
+// Blake2b-224 of the Ed25519 public key of an XPub
+pub fn key_hash(xpub: &[u8]) -> Vec<u8> {
+  let mut hash = vec![0; HASH_SIZE];
+  let mut hasher = Blake2b::new(HASH_SIZE);
+  hasher.input(&xpub[..PUBLIC_KEY_SIZE]);
+  hasher.result(&mut hash);
+  hash
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CredentialKind {
+  Key,
+  Script
+}
+
+pub struct Credential {
+  pub kind: CredentialKind,
+  pub hash: Vec<u8>
+}
+
+impl Credential {
+  pub fn key(xpub: &[u8]) -> Self {
+    Credential { kind: CredentialKind::Key, hash: key_hash(xpub) }
+  }
+
+  fn read(bytes: &[u8], script: bool) -> Result<Self> {
+    if bytes.len() < HASH_SIZE {
+      return Err(Error::TooShort { field: "credential", actual: bytes.len(), min: HASH_SIZE });
+    }
+    let kind = if script { CredentialKind::Script } else { CredentialKind::Key };
+    Ok(Credential { kind: kind, hash: bytes[..HASH_SIZE].to_vec() })
+  }
+
+  fn is_script(&self) -> u8 {
+    if self.kind == CredentialKind::Script { 1 } else { 0 }
+  }
+
+  fn to_js<'a, C: Context<'a>>(&self, cx: &mut C) -> JsResult<'a, JsObject> {
+    let object = cx.empty_object();
+    let kind = cx.string(if self.kind == CredentialKind::Script { "script" } else { "key" });
+    object.set(cx, "type", kind)?;
+    let mut hash = cx.buffer(self.hash.len() as u32)?;
+    {
+      let guard = cx.lock();
+      hash.borrow_mut(&guard).as_mut_slice::<u8>().copy_from_slice(&self.hash);
+    }
+    object.set(cx, "hash", hash)?;
+    Ok(object)
+  }
+}
+
+// Location of the stake key registration certificate
+pub struct Pointer {
+  pub slot: u64,
+  pub tx_index: u64,
+  pub cert_index: u64
+}
+
+// Variable length big endian natural, 7 bits per byte with a continuation bit
+fn write_nat(output: &mut Vec<u8>, value: u64) {
+  let mut groups = vec![(value & 0x7f) as u8];
+  let mut rest = value >> 7;
+  while rest > 0 {
+    groups.push((rest & 0x7f) as u8 | 0x80);
+    rest >>= 7;
+  }
+  groups.reverse();
+  output.extend_from_slice(&groups);
+}
+
+fn read_nat(bytes: &[u8], offset: &mut usize) -> Result<u64> {
+  let mut value: u64 = 0;
+  loop {
+    let byte = *bytes.get(*offset).ok_or_else(|| Error::Decode(String::from("Truncated address pointer")))?;
+    *offset += 1;
+    if value >> 57 != 0 {
+      return Err(Error::Decode(String::from("Address pointer overflows 64 bits")));
+    }
+    value = (value << 7) | (byte & 0x7f) as u64;
+    if byte & 0x80 == 0 { return Ok(value); }
+  }
+}
+
+pub enum ShelleyAddress {
+  Base { network: u8, payment: Credential, stake: Credential },
+  Pointer { network: u8, payment: Credential, pointer: Pointer },
+  Enterprise { network: u8, payment: Credential },
+  Reward { network: u8, stake: Credential }
+}
+
+// Byron addresses and unassigned header types are not Shelley addresses
+pub fn is_shelley_type(header: u8) -> bool {
+  match header >> 4 {
+    TYPE_BASE..=TYPE_ENTERPRISE | 7 | TYPE_REWARD | 15 => true,
+    _ => false
+  }
+}
+
+impl ShelleyAddress {
+  pub fn network(&self) -> u8 {
+    match *self {
+      ShelleyAddress::Base { network, .. } | ShelleyAddress::Pointer { network, .. } |
+      ShelleyAddress::Enterprise { network, .. } | ShelleyAddress::Reward { network, .. } => network
+    }
+  }
+
+  pub fn type_name(&self) -> &'static str {
+    match *self {
+      ShelleyAddress::Base { .. } => "base",
+      ShelleyAddress::Pointer { .. } => "pointer",
+      ShelleyAddress::Enterprise { .. } => "enterprise",
+      ShelleyAddress::Reward { .. } => "reward"
+    }
+  }
+
+  pub fn hrp(&self) -> &'static str {
+    match (self, self.network() == NETWORK_MAINNET) {
+      (&ShelleyAddress::Reward { .. }, true) => "stake",
+      (&ShelleyAddress::Reward { .. }, false) => "stake_test",
+      (_, true) => "addr",
+      (_, false) => "addr_test"
+    }
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(1 + 2 * HASH_SIZE);
+    match *self {
+      ShelleyAddress::Base { network, ref payment, ref stake } => {
+        bytes.push((TYPE_BASE | payment.is_script() | stake.is_script() << 1) << 4 | network);
+        bytes.extend_from_slice(&payment.hash);
+        bytes.extend_from_slice(&stake.hash);
+      },
+      ShelleyAddress::Pointer { network, ref payment, ref pointer } => {
+        bytes.push((TYPE_POINTER | payment.is_script()) << 4 | network);
+        bytes.extend_from_slice(&payment.hash);
+        write_nat(&mut bytes, pointer.slot);
+        write_nat(&mut bytes, pointer.tx_index);
+        write_nat(&mut bytes, pointer.cert_index);
+      },
+      ShelleyAddress::Enterprise { network, ref payment } => {
+        bytes.push((TYPE_ENTERPRISE | payment.is_script()) << 4 | network);
+        bytes.extend_from_slice(&payment.hash);
+      },
+      ShelleyAddress::Reward { network, ref stake } => {
+        bytes.push((TYPE_REWARD | stake.is_script()) << 4 | network);
+        bytes.extend_from_slice(&stake.hash);
+      }
+    }
+    bytes
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+    let header = *bytes.first().ok_or_else(|| Error::Decode(String::from("Empty address")))?;
+    let network = header & 0x0f;
+    let body = &bytes[1..];
+    let (address, size) = match header >> 4 {
+      header_type @ TYPE_BASE..=3 => {
+        let payment = Credential::read(body, header_type & 1 != 0)?;
+        let stake = Credential::read(&body[HASH_SIZE..], header_type & 2 != 0)?;
+        (ShelleyAddress::Base { network: network, payment: payment, stake: stake }, 2 * HASH_SIZE)
+      },
+      header_type @ TYPE_POINTER..=5 => {
+        let payment = Credential::read(body, header_type & 1 != 0)?;
+        let mut offset = HASH_SIZE;
+        let pointer = Pointer {
+          slot: read_nat(body, &mut offset)?,
+          tx_index: read_nat(body, &mut offset)?,
+          cert_index: read_nat(body, &mut offset)?
+        };
+        (ShelleyAddress::Pointer { network: network, payment: payment, pointer: pointer }, offset)
+      },
+      header_type @ TYPE_ENTERPRISE..=7 => {
+        let payment = Credential::read(body, header_type & 1 != 0)?;
+        (ShelleyAddress::Enterprise { network: network, payment: payment }, HASH_SIZE)
+      },
+      header_type @ TYPE_REWARD..=15 => {
+        let stake = Credential::read(body, header_type & 1 != 0)?;
+        (ShelleyAddress::Reward { network: network, stake: stake }, HASH_SIZE)
+      },
+      TYPE_BYRON => return Err(Error::Decode(String::from("Byron addresses are not bech32 encoded"))),
+      header_type => return Err(Error::Decode(format!("Unknown address type {}", header_type)))
+    };
+    if body.len() != size {
+      return Err(Error::InvalidLength { field: "address", actual: bytes.len(), expected: size + 1 });
+    }
+    Ok(address)
+  }
+
+  pub fn to_bech32(&self) -> Result<String> {
+    bech32::encode(self.hrp(), self.to_bytes().to_base32())
+      .map_err(|err| Error::Native(format!("Can't encode bech32: {}", err)))
+  }
+
+  // Checks the human readable part matches the address type and network
+  pub fn from_bech32(hrp: &str, bytes: &[u8]) -> Result<Self> {
+    let address = ShelleyAddress::from_bytes(bytes)?;
+    if address.hrp() != hrp {
+      return Err(Error::Decode(format!("Prefix {} doesn't match a {} address of network {}", hrp, address.type_name(), address.network())));
+    }
+    Ok(address)
+  }
+
+  fn to_js<'a, C: Context<'a>>(&self, cx: &mut C) -> JsResult<'a, JsObject> {
+    let object = cx.empty_object();
+    let type_name = cx.string(self.type_name());
+    object.set(cx, "type", type_name)?;
+    let network = cx.number(self.network() as f64);
+    object.set(cx, "networkId", network)?;
+    match *self {
+      ShelleyAddress::Base { ref payment, ref stake, .. } => {
+        let payment = payment.to_js(cx)?;
+        object.set(cx, "payment", payment)?;
+        let stake = stake.to_js(cx)?;
+        object.set(cx, "stake", stake)?;
+      },
+      ShelleyAddress::Pointer { ref payment, ref pointer, .. } => {
+        let payment = payment.to_js(cx)?;
+        object.set(cx, "payment", payment)?;
+        let pointer_obj = cx.empty_object();
+        for &(key, value) in [("slot", pointer.slot), ("txIndex", pointer.tx_index), ("certIndex", pointer.cert_index)].iter() {
+          let value = cx.number(value as f64);
+          pointer_obj.set(cx, key, value)?;
+        }
+        object.set(cx, "pointer", pointer_obj)?;
+      },
+      ShelleyAddress::Enterprise { ref payment, .. } => {
+        let payment = payment.to_js(cx)?;
+        object.set(cx, "payment", payment)?;
+      },
+      ShelleyAddress::Reward { ref stake, .. } => {
+        let stake = stake.to_js(cx)?;
+        object.set(cx, "stake", stake)?;
+      }
+    }
+    Ok(object)
+  }
+}
+
+// Raw bech32 decoding, callers tell checksum failures apart through the bech32 error
+pub fn decode_bech32(address: &str) -> ::std::result::Result<(String, Vec<u8>), bech32::Error> {
+  let (hrp, data) = bech32::decode(address)?;
+  let bytes = Vec::<u8>::from_base32(&data)?;
+  Ok((hrp, bytes))
+}
+
+pub fn decode(address: &str) -> Result<ShelleyAddress> {
+  let (hrp, bytes) = decode_bech32(address).map_err(|err| Error::Decode(format!("Couldn't decode bech32: {}", err)))?;
+  ShelleyAddress::from_bech32(&hrp, &bytes)
+}
+
+fn xpub_argument(cx: &mut FunctionContext, index: i32, field: &'static str) -> NeonResult<Vec<u8>> {
+  let xpub = cx.argument::<JsBuffer>(index)?;
+  {
+    let guard = cx.lock();
+    let xpub_buf = xpub.borrow(&guard);
+    let xpub_data = xpub_buf.as_slice::<u8>();
+    if xpub_data.len() != hdwallet::XPUB_SIZE {
+      Err(Error::InvalidLength { field: field, actual: xpub_data.len(), expected: hdwallet::XPUB_SIZE })
+    } else {
+      Ok(xpub_data.to_vec())
+    }
+  }.or_throw(cx)
+}
+
+fn encode_address<'a>(cx: &mut FunctionContext<'a>, address: ShelleyAddress) -> JsResult<'a, JsString> {
+  address.to_bech32().and_then(|string| {
+    cx.try_string(&string).map_err(|_| Error::Native(String::from("Can't create JS string")))
+  }).or_throw(cx)
+}
+
+// Params: paymentXPub: Buffer, stakeXPub: Buffer, networkId: Number
+pub fn base_address(mut cx: FunctionContext) -> JsResult<JsString> {
+  let payment = xpub_argument(&mut cx, 0, "payment XPub")?;
+  let stake = xpub_argument(&mut cx, 1, "stake XPub")?;
+  let network = cx.argument::<JsNumber>(2)?.value();
+
+  let network = network_argument(network).or_throw(&mut cx)?;
+  encode_address(&mut cx, ShelleyAddress::Base {
+    network: network, payment: Credential::key(&payment), stake: Credential::key(&stake)
+  })
+}
+
+// Params: paymentXPub: Buffer, slot: Number, txIndex: Number, certIndex: Number, networkId: Number
+pub fn pointer_address(mut cx: FunctionContext) -> JsResult<JsString> {
+  let payment = xpub_argument(&mut cx, 0, "payment XPub")?;
+  let slot = cx.argument::<JsNumber>(1)?.value();
+  let tx_index = cx.argument::<JsNumber>(2)?.value();
+  let cert_index = cx.argument::<JsNumber>(3)?.value();
+  let network = cx.argument::<JsNumber>(4)?.value();
+
+  let pointer = u32_argument(tx_index, "tx index").and_then(|tx_index| {
+    let cert_index = u32_argument(cert_index, "cert index")?;
+    if slot.fract() != 0.0 || slot < 0.0 || slot > 9007199254740991.0 {
+      return Err(Error::InvalidArgument(format!("Wrong slot {} should be a safe unsigned integer", slot)));
+    }
+    Ok(Pointer { slot: slot as u64, tx_index: tx_index as u64, cert_index: cert_index as u64 })
+  }).or_throw(&mut cx)?;
+  let network = network_argument(network).or_throw(&mut cx)?;
+  encode_address(&mut cx, ShelleyAddress::Pointer {
+    network: network, payment: Credential::key(&payment), pointer: pointer
+  })
+}
+
+// Params: paymentXPub: Buffer, networkId: Number
+pub fn enterprise_address(mut cx: FunctionContext) -> JsResult<JsString> {
+  let payment = xpub_argument(&mut cx, 0, "payment XPub")?;
+  let network = cx.argument::<JsNumber>(1)?.value();
+
+  let network = network_argument(network).or_throw(&mut cx)?;
+  encode_address(&mut cx, ShelleyAddress::Enterprise { network: network, payment: Credential::key(&payment) })
+}
+
+// Params: stakeXPub: Buffer, networkId: Number
+pub fn reward_address(mut cx: FunctionContext) -> JsResult<JsString> {
+  let stake = xpub_argument(&mut cx, 0, "stake XPub")?;
+  let network = cx.argument::<JsNumber>(1)?.value();
+
+  let network = network_argument(network).or_throw(&mut cx)?;
+  encode_address(&mut cx, ShelleyAddress::Reward { network: network, stake: Credential::key(&stake) })
+}
+
+// Params: address: String (bech32)
+// Returns: { type: String, networkId: Number, payment: Credential?, stake: Credential?, pointer: Pointer? }
+pub fn decode_address(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let address = cx.argument::<JsString>(0)?.value();
+
+  let decoded = decode(&address).or_throw(&mut cx)?;
+  decoded.to_js(&mut cx)
+}
+
+// Addresses of account chain keys, staked with the first key of the staking chain
+// unless `enterprise` is set.
+// Params: accountXPub: Buffer, chain: Number, indices: Array<Number>, networkId: Number, enterprise: Boolean
+pub fn account_addresses(mut cx: FunctionContext) -> JsResult<JsArray> {
+  let account = xpub_argument(&mut cx, 0, "XPub")?;
+  let chain = cx.argument::<JsNumber>(1)?.value();
+  let indices_arr = cx.argument::<JsArray>(2)?.to_vec(&mut cx)?;
+  let network = cx.argument::<JsNumber>(3)?.value();
+  let enterprise = cx.argument::<JsBoolean>(4)?.value();
+
+  let mut indices = Vec::with_capacity(indices_arr.len());
+  for index in indices_arr {
+    let index = index.downcast_or_throw::<JsNumber, _>(&mut cx)?.value();
+    indices.push(u32_argument(index, "index").or_throw(&mut cx)?);
+  }
+
+  let addresses = network_argument(network).and_then(|network| {
+    let chain = u32_argument(chain, "chain")?;
+    let mut stake_xpub = [0 as u8; hdwallet::XPUB_SIZE];
+    derive_public_indices(account.as_ptr(), &[CHAIN_STAKING, 0], stake_xpub.as_mut_ptr())?;
+    let mut xpub = [0 as u8; hdwallet::XPUB_SIZE];
+    indices.iter().map(|index| {
+      derive_public_indices(account.as_ptr(), &[chain, *index], xpub.as_mut_ptr())?;
+      let address = if enterprise {
+        ShelleyAddress::Enterprise { network: network, payment: Credential::key(&xpub) }
+      } else {
+        ShelleyAddress::Base { network: network, payment: Credential::key(&xpub), stake: Credential::key(&stake_xpub) }
+      };
+      address.to_bech32()
+    }).collect::<Result<Vec<String>>>()
+  }).or_throw(&mut cx)?;
+
+  let array = JsArray::new(&mut cx, addresses.len() as u32);
+  for (index, address) in addresses.iter().enumerate() {
+    let address = cx.string(address);
+    array.set(&mut cx, index as u32, address)?;
+  }
+  Ok(array)
+}
+
+// m/1852'/1815'/account'/chain/index
+pub fn key_path(account: u32, chain: u32, index: u32) -> Vec<u32> {
+  vec![PURPOSE_SHELLEY | HARDENED_INDEX, COIN_TYPE | HARDENED_INDEX, account | HARDENED_INDEX, chain, index]
+}
+
+// Derives the XPub of m/1852'/1815'/account' from the root XPrv at `xprv`
+pub fn derive_account_xpub(xprv: *const u8, account: u32, output: *mut u8) -> Result<()> {
+  if account >= HARDENED_INDEX {
+    return Err(Error::InvalidArgument(format!("Wrong account {} should be below {}", account, HARDENED_INDEX)));
+  }
+  let mut account_xprv = SecureBuffer::new(hdwallet::XPRV_SIZE);
+  derive_private_indices(xprv, &key_path(account, 0, 0)[..3], account_xprv.as_mut_ptr());
+  wallet_wasm::wallet_to_public(account_xprv.as_ptr(), output);
+  Ok(())
+}
+
+// Hash of the first key of the staking chain of an account
+pub fn stake_key_hash(account_xpub: &[u8]) -> Result<Vec<u8>> {
+  let mut stake_xpub = [0 as u8; hdwallet::XPUB_SIZE];
+  derive_public_indices(account_xpub.as_ptr(), &[CHAIN_STAKING, 0], stake_xpub.as_mut_ptr())?;
+  Ok(key_hash(&stake_xpub))
+}
+
+// Params: xprv: Buffer, account: Number
+// Returns: account XPub of m/1852'/1815'/account'
+pub fn account_xpub(mut cx: FunctionContext) -> JsResult<JsBuffer> {
+  let xprv = cx.argument::<JsBuffer>(0)?;
+  let account = cx.argument::<JsNumber>(1)?.value();
+  let mut output_buf = cx.buffer(hdwallet::XPUB_SIZE as u32)?;
+
+  {
+    let guard = cx.lock();
+    let xprv_buf: BufferPtr = xprv.borrow(&guard).into();
+    let output: MutBufferPtr = output_buf.borrow_mut(&guard).into();
+
+    handle_exception(|| {
+      if xprv_buf.size != hdwallet::XPRV_SIZE {
+        return Err(Error::InvalidLength { field: "XPrv", actual: xprv_buf.size, expected: hdwallet::XPRV_SIZE });
+      }
+      let account = u32_argument(account, "account")?;
+      derive_account_xpub(xprv_buf.ptr, account, output.ptr)
+    }).map(|_| output_buf )
+  }.or_throw(&mut cx)
+}
+
+// Params: accountXPub: Buffer
+// Returns: { xpub: Buffer, hash: Buffer } of the first key of the staking chain
+pub fn stake_key(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let account = xpub_argument(&mut cx, 0, "XPub")?;
+
+  let mut stake_xpub = [0 as u8; hdwallet::XPUB_SIZE];
+  derive_public_indices(account.as_ptr(), &[CHAIN_STAKING, 0], stake_xpub.as_mut_ptr()).or_throw(&mut cx)?;
+
+  let object = cx.empty_object();
+  for &(key, data) in [("xpub", &stake_xpub[..]), ("hash", &key_hash(&stake_xpub)[..])].iter() {
+    let mut buffer = cx.buffer(data.len() as u32)?;
+    {
+      let guard = cx.lock();
+      buffer.borrow_mut(&guard).as_mut_slice::<u8>().copy_from_slice(data);
+    }
+    object.set(&mut cx, key, buffer)?;
+  }
+  Ok(object)
+}
@@ -0,0 +1,552 @@
+use neon::prelude::*;
+use exception::*;
+use buffer::*;
+use json::*;
+use params::ProtocolParams;
+use address::{decode_base58, decode_byron, is_bech32_era};
+use hdwallet::{derive_private_indices, format_path, parse_path};
+use shelley::{self, Credential, CredentialKind, ShelleyAddress, CHAIN_STAKING, HASH_SIZE};
+use wallet_wasm;
+use cbor;
+use cardano::hdwallet;
+use cardano::util::hex;
+use cbor_event::Type;
+use cryptoxide::blake2b::Blake2b;
+use cryptoxide::digest::Digest;
+use std::cmp;
+use std::collections::BTreeSet;
+
+pub const TX_ID_SIZE: usize = 32;
+// Fee calculation converges in a couple of rounds, bail out if it doesn't
+pub const MAX_FEE_ROUNDS: usize = 16;
+
+const BODY_INPUTS: u64 = 0;
+const BODY_OUTPUTS: u64 = 1;
+const BODY_FEE: u64 = 2;
+const BODY_TTL: u64 = 3;
+const BODY_CERTIFICATES: u64 = 4;
+const BODY_WITHDRAWALS: u64 = 5;
+const WITNESS_VKEYS: u64 = 0;
+const WITNESS_BOOTSTRAP: u64 = 2;
+
+#[derive(Deserialize)]
+pub struct TxPointer {
+  pub id: String,
+  pub index: u32
+}
+
+#[derive(Deserialize)]
+pub struct OutputRequest {
+  pub address: String,
+  pub value: String
+}
+
+#[derive(Deserialize)]
+pub struct Addressing {
+  pub account: u32,
+  pub change: u32,
+  pub index: u32
+}
+
+#[derive(Deserialize)]
+pub struct InputRequest {
+  pub ptr: TxPointer,
+  pub value: OutputRequest,
+  pub addressing: Addressing
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CertificateRequest {
+  StakeRegistration { account: u32 },
+  StakeDeregistration { account: u32 },
+  StakeDelegation { account: u32, pool: String }
+}
+
+impl CertificateRequest {
+  fn account(&self) -> u32 {
+    match *self {
+      CertificateRequest::StakeRegistration { account } |
+      CertificateRequest::StakeDeregistration { account } |
+      CertificateRequest::StakeDelegation { account, .. } => account
+    }
+  }
+}
+
+#[derive(Deserialize)]
+pub struct WithdrawalRequest {
+  pub account: u32,
+  pub value: String
+}
+
+#[derive(Deserialize)]
+pub struct WalletRequest {
+  pub root_cached_key: SecureString
+}
+
+#[derive(Deserialize)]
+pub struct TxRequest {
+  pub inputs: Vec<InputRequest>,
+  pub outputs: Vec<OutputRequest>,
+  pub change_addr: String,
+  pub ttl: u64,
+  #[serde(default)]
+  pub certificates: Vec<CertificateRequest>,
+  #[serde(default)]
+  pub withdrawals: Vec<WithdrawalRequest>,
+  pub network_id: u8,
+  #[serde(default)]
+  pub protocol_params: Option<ProtocolParams>
+}
+
+impl TxRequest {
+  pub fn params(&self) -> ProtocolParams {
+    self.protocol_params.unwrap_or_else(ProtocolParams::shelley)
+  }
+}
+
+#[derive(Deserialize)]
+struct SpendRequest {
+  wallet: WalletRequest,
+  #[serde(flatten)]
+  tx: TxRequest
+}
+
+// CIP-1852 account XPub with its index under m/1852'/1815'
+#[derive(Deserialize)]
+pub struct AccountRequest {
+  pub root_cached_key: String,
+  pub account: u32
+}
+
+#[derive(Deserialize)]
+struct UnsignedRequest {
+  account: AccountRequest,
+  #[serde(flatten)]
+  tx: TxRequest
+}
+
+pub fn parse_coin(value: &str) -> Result<u64> {
+  value.parse::<u64>().map_err(|_| Error::Decode(format!("Invalid coin value {}", value)))
+}
+
+pub fn sum_coins<I: Iterator<Item = u64>>(coins: I) -> Result<u64> {
+  let mut total: u64 = 0;
+  for coin in coins {
+    total = total.checked_add(coin).ok_or_else(|| Error::InvalidArgument(String::from("Coin total overflows")))?;
+  }
+  Ok(total)
+}
+
+// Binary form of a bech32 Shelley or base58 Byron address
+pub fn address_bytes(address: &str) -> Result<Vec<u8>> {
+  if is_bech32_era(address) {
+    return shelley::decode(address).map(|decoded| decoded.to_bytes());
+  }
+  let bytes = decode_base58(address)?;
+  decode_byron(&bytes)?;
+  Ok(bytes)
+}
+
+pub fn decode_fixed_hex(string: &str, field: &'static str, size: usize) -> Result<Vec<u8>> {
+  let bytes = hex::decode(string).map_err(|err| Error::Decode(format!("Invalid {} hex: {:?}", field, err)))?;
+  if bytes.len() != size {
+    return Err(Error::InvalidLength { field: field, actual: bytes.len(), expected: size });
+  }
+  Ok(bytes)
+}
+
+pub fn tx_hash(body: &[u8]) -> [u8; TX_ID_SIZE] {
+  let mut hash = [0 as u8; TX_ID_SIZE];
+  let mut hasher = Blake2b::new(TX_ID_SIZE);
+  hasher.input(body);
+  hasher.result(&mut hash);
+  hash
+}
+
+enum Certificate {
+  Registration(Vec<u8>),
+  Deregistration(Vec<u8>),
+  Delegation(Vec<u8>, Vec<u8>)
+}
+
+impl Certificate {
+  fn encode(&self, writer: &mut cbor::Writer) {
+    fn credential(writer: &mut cbor::Writer, hash: &[u8]) {
+      writer.array(2).unsigned(0).bytes(hash);
+    }
+    match *self {
+      Certificate::Registration(ref hash) => { writer.array(2).unsigned(0); credential(writer, hash) },
+      Certificate::Deregistration(ref hash) => { writer.array(2).unsigned(1); credential(writer, hash) },
+      Certificate::Delegation(ref hash, ref pool) => {
+        writer.array(3).unsigned(2);
+        credential(writer, hash);
+        writer.bytes(pool);
+      }
+    }
+  }
+}
+
+// Balanced transaction body with the keys needed to sign it
+pub struct Plan {
+  inputs: Vec<(Vec<u8>, u32)>,
+  outputs: Vec<(Vec<u8>, u64)>,
+  change: Option<(Vec<u8>, u64)>,
+  pub fee: u64,
+  ttl: u64,
+  certificates: Vec<Certificate>,
+  withdrawals: Vec<(Vec<u8>, u64)>,
+  pub deposit: u64,
+  pub refund: u64,
+  // Derivation paths from the wallet root of the required witnesses
+  pub paths: Vec<Vec<u32>>
+}
+
+impl Plan {
+  // Plain payment of `outputs`, without change, certificates nor withdrawals
+  pub fn payment(inputs: Vec<(Vec<u8>, u32)>, outputs: Vec<(Vec<u8>, u64)>, fee: u64, ttl: u64) -> Plan {
+    Plan {
+      inputs: inputs, outputs: outputs, change: None, fee: fee, ttl: ttl,
+      certificates: Vec::new(), withdrawals: Vec::new(), deposit: 0, refund: 0, paths: Vec::new()
+    }
+  }
+
+  pub fn change_used(&self) -> bool {
+    self.change.is_some()
+  }
+
+  pub fn encode_body(&self) -> Vec<u8> {
+    let mut fields = 4;
+    if !self.certificates.is_empty() { fields += 1; }
+    if !self.withdrawals.is_empty() { fields += 1; }
+
+    let mut writer = cbor::Writer::new();
+    writer.map(fields);
+    writer.unsigned(BODY_INPUTS).array(self.inputs.len());
+    for &(ref id, index) in self.inputs.iter() {
+      writer.array(2).bytes(id).unsigned(index as u64);
+    }
+    writer.unsigned(BODY_OUTPUTS).array(self.outputs.len() + if self.change.is_some() { 1 } else { 0 });
+    for &(ref address, value) in self.outputs.iter().chain(self.change.iter()) {
+      writer.array(2).bytes(address).unsigned(value);
+    }
+    writer.unsigned(BODY_FEE).unsigned(self.fee);
+    writer.unsigned(BODY_TTL).unsigned(self.ttl);
+    if !self.certificates.is_empty() {
+      writer.unsigned(BODY_CERTIFICATES).array(self.certificates.len());
+      for certificate in self.certificates.iter() {
+        certificate.encode(&mut writer);
+      }
+    }
+    if !self.withdrawals.is_empty() {
+      writer.unsigned(BODY_WITHDRAWALS).map(self.withdrawals.len());
+      for &(ref address, value) in self.withdrawals.iter() {
+        writer.bytes(address).unsigned(value);
+      }
+    }
+    writer.into_bytes()
+  }
+}
+
+// Witness set with one VKey witness per `(public key, signature)` pair
+pub fn encode_witnesses(witnesses: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+  let mut writer = cbor::Writer::new();
+  writer.map(1).unsigned(WITNESS_VKEYS).array(witnesses.len());
+  for &(ref vkey, ref signature) in witnesses.iter() {
+    writer.array(2).bytes(vkey).bytes(signature);
+  }
+  writer.into_bytes()
+}
+
+// Bootstrap witness of a Byron address key: the signature, the chain code and the
+// address attributes let the ledger rebuild the address root
+pub struct BootstrapWitness {
+  pub public_key: Vec<u8>,
+  pub signature: Vec<u8>,
+  pub chain_code: Vec<u8>,
+  pub attributes: Vec<u8>
+}
+
+// Witness set with the bootstrap witnesses spending Byron outputs
+pub fn encode_bootstrap_witnesses(witnesses: &[BootstrapWitness]) -> Vec<u8> {
+  let mut writer = cbor::Writer::new();
+  writer.map(1).unsigned(WITNESS_BOOTSTRAP).array(witnesses.len());
+  for witness in witnesses.iter() {
+    writer.array(4).bytes(&witness.public_key).bytes(&witness.signature)
+      .bytes(&witness.chain_code).bytes(&witness.attributes);
+  }
+  writer.into_bytes()
+}
+
+// `[body, witnesses, is_valid, auxiliary data]`
+pub fn encode_transaction(body: &[u8], witnesses: &[u8]) -> Vec<u8> {
+  let mut writer = cbor::Writer::new();
+  writer.array(4).raw(body).raw(witnesses).boolean(true).null();
+  writer.into_bytes()
+}
+
+// Size of the signed transaction, placeholder witnesses have the size of real ones
+pub fn signed_size(body: &[u8], witnesses: usize) -> usize {
+  let placeholder = (vec![0; 32], vec![0; hdwallet::SIGNATURE_SIZE]);
+  let witnesses: Vec<(Vec<u8>, Vec<u8>)> = (0..witnesses).map(|_| placeholder.clone()).collect();
+  encode_transaction(body, &encode_witnesses(&witnesses)).len()
+}
+
+// Selects nothing: spends every given input, pays the outputs, deposits and fee
+// and sends the rest to the change address when it is worth an output.
+// `account_xpub` gives the XPub of a CIP-1852 account of the wallet.
+pub fn plan<F>(request: &TxRequest, params: &ProtocolParams, account_xpub: F) -> Result<Plan>
+  where F: Fn(u32) -> Result<Vec<u8>>
+{
+  if request.inputs.is_empty() {
+    return Err(Error::InvalidArgument(String::from("Transaction has no inputs")));
+  }
+  let network = shelley::check_network_id(request.network_id as u32)?;
+  let mut paths = BTreeSet::new();
+  let mut accounts = BTreeSet::new();
+  let mut inputs = Vec::with_capacity(request.inputs.len());
+  for input in request.inputs.iter() {
+    // Rejects inputs of accounts the keys can't be derived for
+    if accounts.insert(input.addressing.account) {
+      account_xpub(input.addressing.account)?;
+    }
+    inputs.push((decode_fixed_hex(&input.ptr.id, "transaction id", TX_ID_SIZE)?, input.ptr.index));
+    paths.insert(shelley::key_path(input.addressing.account, input.addressing.change, input.addressing.index));
+  }
+  let mut outputs = Vec::with_capacity(request.outputs.len());
+  for output in request.outputs.iter() {
+    let value = parse_coin(&output.value)?;
+    if value < params.min_utxo {
+      return Err(Error::InvalidArgument(format!("Output of {} is below the minimum UTxO value {}", value, params.min_utxo)));
+    }
+    outputs.push((address_bytes(&output.address)?, value));
+  }
+
+  let mut certificates = Vec::with_capacity(request.certificates.len());
+  let (mut deposit, mut refund) = (0, 0);
+  for certificate in request.certificates.iter() {
+    let account = certificate.account();
+    let hash = shelley::stake_key_hash(&account_xpub(account)?)?;
+    certificates.push(match *certificate {
+      CertificateRequest::StakeRegistration { .. } => {
+        deposit += params.key_deposit;
+        Certificate::Registration(hash)
+      },
+      CertificateRequest::StakeDeregistration { .. } => {
+        refund += params.key_deposit;
+        paths.insert(shelley::key_path(account, CHAIN_STAKING, 0));
+        Certificate::Deregistration(hash)
+      },
+      CertificateRequest::StakeDelegation { ref pool, .. } => {
+        paths.insert(shelley::key_path(account, CHAIN_STAKING, 0));
+        Certificate::Delegation(hash, decode_fixed_hex(pool, "pool key hash", HASH_SIZE)?)
+      }
+    });
+  }
+
+  let mut withdrawals = Vec::with_capacity(request.withdrawals.len());
+  for withdrawal in request.withdrawals.iter() {
+    let hash = shelley::stake_key_hash(&account_xpub(withdrawal.account)?)?;
+    let address = ShelleyAddress::Reward {
+      network: network, stake: Credential { kind: CredentialKind::Key, hash: hash }
+    };
+    withdrawals.push((address.to_bytes(), parse_coin(&withdrawal.value)?));
+    paths.insert(shelley::key_path(withdrawal.account, CHAIN_STAKING, 0));
+  }
+
+  let available = sum_coins(request.inputs.iter().map(|input| parse_coin(&input.value.value)).collect::<Result<Vec<u64>>>()?.into_iter())?
+    .checked_add(sum_coins(withdrawals.iter().map(|&(_, value)| value))?)
+    .and_then(|total| total.checked_add(refund))
+    .ok_or_else(|| Error::InvalidArgument(String::from("Coin total overflows")))?;
+  let spent = sum_coins(outputs.iter().map(|&(_, value)| value))?
+    .checked_add(deposit)
+    .ok_or_else(|| Error::InvalidArgument(String::from("Coin total overflows")))?;
+
+  let mut plan = Plan {
+    inputs: inputs, outputs: outputs, change: None, fee: 0, ttl: request.ttl,
+    certificates: certificates, withdrawals: withdrawals, deposit: deposit, refund: refund,
+    paths: paths.into_iter().collect()
+  };
+  let change_address = address_bytes(&request.change_addr)?;
+
+  let mut fee = params.fee_constant;
+  for _ in 0..MAX_FEE_ROUNDS {
+    let needed = spent.saturating_add(fee);
+    let left = available.checked_sub(needed)
+      .ok_or(Error::InsufficientFunds { available: available, needed: needed })?;
+    // Dust change is left to the fee
+    if left >= cmp::max(params.min_utxo, 1) {
+      plan.change = Some((change_address.clone(), left));
+      plan.fee = fee;
+    } else {
+      plan.change = None;
+      plan.fee = fee + left;
+    }
+    let size = signed_size(&plan.encode_body(), plan.paths.len());
+    if size > params.max_tx_size {
+      return Err(Error::TransactionTooLarge { size: size, max: params.max_tx_size });
+    }
+    let min_fee = params.min_fee(size);
+    if min_fee <= plan.fee {
+      return Ok(plan);
+    }
+    fee = min_fee;
+  }
+  Err(Error::Native(String::from("Fee calculation didn't converge")))
+}
+
+// Signs the body hash with the key of every path from the root XPrv at `xprv`
+pub fn sign_body(body: &[u8], xprv: *const u8, paths: &[Vec<u32>]) -> Vec<(Vec<u8>, Vec<u8>)> {
+  let hash = tx_hash(body);
+  let mut key = SecureBuffer::new(hdwallet::XPRV_SIZE);
+  let mut xpub = [0 as u8; hdwallet::XPUB_SIZE];
+  paths.iter().map(|path| {
+    derive_private_indices(xprv, path, key.as_mut_ptr());
+    wallet_wasm::wallet_to_public(key.as_ptr(), xpub.as_mut_ptr());
+    let mut signature = vec![0; hdwallet::SIGNATURE_SIZE];
+    wallet_wasm::wallet_sign(key.as_ptr(), hash.as_ptr(), hash.len(), signature.as_mut_ptr());
+    (xpub[..32].to_vec(), signature)
+  }).collect()
+}
+
+pub fn root_key(wallet: &WalletRequest) -> Result<SecureBuffer> {
+  let xprv = decode_hex_secure(&wallet.root_cached_key, "root key")?;
+  if xprv.len() != hdwallet::XPRV_SIZE {
+    return Err(Error::InvalidLength { field: "XPrv", actual: xprv.len(), expected: hdwallet::XPRV_SIZE });
+  }
+  Ok(xprv)
+}
+
+pub fn set_coin<'a, C: Context<'a>>(cx: &mut C, object: Handle<'a, JsObject>, key: &str, value: u64) -> NeonResult<()> {
+  let value = cx.string(value.to_string());
+  object.set(cx, key, value).map(|_| ())
+}
+
+pub fn set_bytes<'a, C: Context<'a>>(cx: &mut C, object: Handle<'a, JsObject>, key: &str, data: &[u8]) -> NeonResult<()> {
+  let mut buffer = cx.buffer(data.len() as u32)?;
+  {
+    let guard = cx.lock();
+    buffer.borrow_mut(&guard).as_mut_slice::<u8>().copy_from_slice(data);
+  }
+  object.set(cx, key, buffer).map(|_| ())
+}
+
+// The only account an online builder can derive keys for
+pub fn account_key(account: &AccountRequest) -> Result<impl Fn(u32) -> Result<Vec<u8>>> {
+  let xpub = decode_fixed_hex(&account.root_cached_key, "XPub", hdwallet::XPUB_SIZE)?;
+  let index = account.account;
+  Ok(move |requested| {
+    if requested != index {
+      return Err(Error::InvalidArgument(format!("Account {} is not the account {} of the XPub", requested, index)));
+    }
+    Ok(xpub.clone())
+  })
+}
+
+fn plan_summary<'a>(cx: &mut FunctionContext<'a>, plan: &Plan, body: &[u8]) -> JsResult<'a, JsObject> {
+  let object = cx.empty_object();
+  let tx_id = cx.string(hex::encode(&tx_hash(body)));
+  object.set(cx, "tx_id", tx_id)?;
+  set_coin(cx, object, "fee", plan.fee)?;
+  set_coin(cx, object, "deposit", plan.deposit)?;
+  set_coin(cx, object, "refund", plan.refund)?;
+  let change_used = cx.boolean(plan.change_used());
+  object.set(cx, "change_used", change_used)?;
+  Ok(object)
+}
+
+// Params: params: JSONString
+// Returns: { cbor_encoded_tx: Buffer, tx_id: String, fee: String, deposit: String, refund: String, change_used: Boolean }
+pub fn spend(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+
+  let (plan, body, tx) = parse_request::<SpendRequest>(&params).and_then(|request| {
+    let xprv = root_key(&request.wallet)?;
+    handle_exception(|| {
+      let plan = plan(&request.tx, &request.tx.params(), |account| {
+        let mut xpub = vec![0; hdwallet::XPUB_SIZE];
+        shelley::derive_account_xpub(xprv.as_ptr(), account, xpub.as_mut_ptr())?;
+        Ok(xpub)
+      })?;
+      let body = plan.encode_body();
+      let witnesses = sign_body(&body, xprv.as_ptr(), &plan.paths);
+      let tx = encode_transaction(&body, &encode_witnesses(&witnesses));
+      Ok((plan, body, tx))
+    })
+  }).or_throw(&mut cx)?;
+
+  let object = plan_summary(&mut cx, &plan, &body)?;
+  set_bytes(&mut cx, object, "cbor_encoded_tx", &tx)?;
+  Ok(object)
+}
+
+// Builds the transaction body from an account XPub, without any private key.
+// Only Shelley inputs are supported, Byron ones need the root key of `byron_tx`.
+// Params: params: JSONString
+// Returns: { body: Buffer, paths: Array<String>, tx_id: String, fee: String, deposit: String, refund: String,
+//            change_used: Boolean }
+pub fn build_unsigned(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let params = cx.argument::<JsString>(0)?.value();
+
+  let (plan, body) = parse_request::<UnsignedRequest>(&params).and_then(|request| {
+    let account_xpub = account_key(&request.account)?;
+    handle_exception(|| {
+      let plan = plan(&request.tx, &request.tx.params(), account_xpub)?;
+      let body = plan.encode_body();
+      Ok((plan, body))
+    })
+  }).or_throw(&mut cx)?;
+
+  let object = plan_summary(&mut cx, &plan, &body)?;
+  set_bytes(&mut cx, object, "body", &body)?;
+  let paths = JsArray::new(&mut cx, plan.paths.len() as u32);
+  for (index, path) in plan.paths.iter().enumerate() {
+    let path = cx.string(format_path(path));
+    paths.set(&mut cx, index as u32, path)?;
+  }
+  object.set(&mut cx, "paths", paths)?;
+  Ok(object)
+}
+
+// Adds the witnesses of the root XPrv keys at `paths` to an unsigned body.
+// Params: xprv: Buffer, body: Buffer, paths: Array<String>
+// Returns: signed transaction Buffer
+pub fn sign(mut cx: FunctionContext) -> JsResult<JsBuffer> {
+  let xprv = cx.argument::<JsBuffer>(0)?;
+  let body = cx.argument::<JsBuffer>(1)?;
+  let paths_arr = cx.argument::<JsArray>(2)?.to_vec(&mut cx)?;
+
+  let mut paths = Vec::with_capacity(paths_arr.len());
+  for path in paths_arr {
+    let path = path.downcast_or_throw::<JsString, _>(&mut cx)?.value();
+    paths.push(parse_path(&path).or_throw(&mut cx)?);
+  }
+
+  let tx = {
+    let guard = cx.lock();
+    let xprv_buf: BufferPtr = xprv.borrow(&guard).into();
+    let body_buf = body.borrow(&guard);
+    let body_data = body_buf.as_slice::<u8>();
+
+    handle_exception(|| {
+      if xprv_buf.size != hdwallet::XPRV_SIZE {
+        return Err(Error::InvalidLength { field: "XPrv", actual: xprv_buf.size, expected: hdwallet::XPRV_SIZE });
+      }
+      let mut reader = cbor::reader(body_data);
+      if reader.cbor_type()? != Type::Map {
+        return Err(cbor::error(&reader, "expected a transaction body map"));
+      }
+      cbor::skip(&mut reader)?;
+      cbor::finish(&reader)?;
+      let witnesses = sign_body(body_data, xprv_buf.ptr, &paths);
+      Ok(encode_transaction(body_data, &encode_witnesses(&witnesses)))
+    })
+  }.or_throw(&mut cx)?;
+
+  let mut output = cx.buffer(tx.len() as u32)?;
+  {
+    let guard = cx.lock();
+    output.borrow_mut(&guard).as_mut_slice::<u8>().copy_from_slice(&tx);
+  }
+  Ok(output)
+}
@@ -0,0 +1,68 @@
+use neon::prelude::*;
+use exception::*;
+use buffer::*;
+use json::*;
+
+// Native result of a request, converted to JS on the main thread
+pub trait ToJs: Send + 'static {
+  fn to_js<'a, C: Context<'a>>(&self, cx: &mut C) -> JsResult<'a, JsObject>;
+}
+
+// Runs a native request handler on the libuv thread pool
+// and passes its result to the JS callback.
+pub struct RequestTask<T> {
+  func: fn(&str) -> Result<T>,
+  params: SecureString
+}
+
+impl<T> RequestTask<T> {
+  pub fn new(func: fn(&str) -> Result<T>, params: SecureString) -> Self {
+    RequestTask { func: func, params: params }
+  }
+}
+
+impl<T: ToJs> Task for RequestTask<T> {
+  type Output = T;
+  type Error = Error;
+  type JsEvent = JsObject;
+
+  fn perform(&self) -> Result<T> {
+    let (func, params) = (self.func, &self.params);
+    handle_exception(|| func(params))
+  }
+
+  fn complete(self, mut cx: TaskContext, result: Result<T>) -> JsResult<JsObject> {
+    let output = result.or_throw(&mut cx)?;
+    output.to_js(&mut cx)
+  }
+}
+
+// Runs a JSON based wallet_wasm function on the libuv thread pool
+// and passes the resulting JSON string to the JS callback.
+pub struct JsonTask {
+  func: JsonFunc,
+  params: SecureString,
+  capacity: usize
+}
+
+impl JsonTask {
+  pub fn new(func: JsonFunc, params: SecureString, capacity: usize) -> Self {
+    JsonTask { func: func, params: params, capacity: capacity }
+  }
+}
+
+impl Task for JsonTask {
+  type Output = SecureString;
+  type Error = Error;
+  type JsEvent = JsString;
+
+  fn perform(&self) -> Result<SecureString> {
+    call_json(self.func, &self.params, self.capacity)
+  }
+
+  fn complete(self, mut cx: TaskContext, result: Result<SecureString>) -> JsResult<JsString> {
+    result.and_then(|response| {
+      cx.try_string(&*response).map_err(|_| Error::Native(String::from("Can't create JS string")))
+    }).or_throw(&mut cx)
+  }
+}
@@ -0,0 +1,533 @@
+use neon::prelude::*;
+use exception::*;
+use json::*;
+use cbor;
+use address::{byron_root, decode_byron, u32_argument, ByronAddressType};
+use hdwallet::{verify_signature, PUBLIC_KEY_SIZE};
+use shelley::{self, CredentialKind, ShelleyAddress};
+use shelley_tx::{address_bytes, parse_coin, set_bytes, set_coin, sum_coins, tx_hash, OutputRequest};
+use cardano::hdwallet;
+use cardano::util::{base58, hex};
+use cbor_event::{Len, Type};
+
+// Shelley era sets can be wrapped in the CBOR set tag
+const TAG_SET: u64 = 258;
+
+const BODY_INPUTS: u64 = 0;
+const BODY_OUTPUTS: u64 = 1;
+const BODY_FEE: u64 = 2;
+const BODY_TTL: u64 = 3;
+const WITNESS_VKEYS: u64 = 0;
+const WITNESS_BOOTSTRAP: u64 = 2;
+
+// Byron signing tags prefixed to the signed data
+pub const SIGN_TX: u8 = 0x01;
+const SIGN_REDEEM_TX: u8 = 0x02;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Era {
+  Byron,
+  Shelley
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WitnessKind {
+  PubKey,
+  Script,
+  Redeem,
+  VKey,
+  Bootstrap
+}
+
+impl WitnessKind {
+  fn name(&self) -> &'static str {
+    match *self {
+      WitnessKind::PubKey => "pubkey",
+      WitnessKind::Script => "script",
+      WitnessKind::Redeem => "redeem",
+      WitnessKind::VKey => "vkey",
+      WitnessKind::Bootstrap => "bootstrap"
+    }
+  }
+}
+
+pub struct TxInput {
+  pub id: Vec<u8>,
+  pub index: u32
+}
+
+pub struct TxOutput {
+  // Binary address, Byron addresses keep their CBOR encoding
+  pub address: Vec<u8>,
+  pub value: u64,
+  pub has_assets: bool
+}
+
+// Byron witnesses carry the XPub, Shelley ones the Ed25519 key
+// (and the chain code for bootstrap witnesses).
+pub struct Witness {
+  pub kind: WitnessKind,
+  pub public_key: Vec<u8>,
+  pub signature: Vec<u8>,
+  pub chain_code: Vec<u8>,
+  pub attributes: Vec<u8>
+}
+
+pub struct DecodedTx<'a> {
+  pub era: Era,
+  pub tx_id: [u8; 32],
+  pub size: usize,
+  // Hashed and signed part: the Byron `Tx` or the Shelley body
+  pub body: &'a [u8],
+  pub inputs: Vec<TxInput>,
+  pub outputs: Vec<TxOutput>,
+  pub attributes: Option<&'a [u8]>,
+  pub fee: Option<u64>,
+  pub ttl: Option<u64>,
+  pub witnesses: Vec<Witness>
+}
+
+fn read_u32(reader: &mut cbor::Reader, field: &str) -> Result<u32> {
+  let value = reader.unsigned_integer()?;
+  if value > u32::max_value() as u64 {
+    return Err(cbor::error(reader, &format!("{} {} is larger than 32 bits", field, value)));
+  }
+  Ok(value as u32)
+}
+
+fn read_array<'a, T, F>(reader: &mut cbor::Reader<'a>, mut read: F) -> Result<Vec<T>>
+  where F: FnMut(&mut cbor::Reader<'a>) -> Result<T>
+{
+  let length = reader.array()?;
+  let mut items = Vec::new();
+  let mut index = 0;
+  while cbor::has_next(reader, length, index)? {
+    items.push(read(reader)?);
+    index += 1;
+  }
+  Ok(items)
+}
+
+// `[0, #6.24(bytes .cbor [txid, index])]`
+fn read_byron_input(reader: &mut cbor::Reader) -> Result<TxInput> {
+  reader.tuple(2, "input")?;
+  if reader.unsigned_integer()? != 0 {
+    return Err(cbor::error(reader, "unknown input type"));
+  }
+  let pointer = cbor::encoded_cbor(reader)?;
+  let mut inner = cbor::reader(&pointer);
+  inner.tuple(2, "input pointer")?;
+  let input = TxInput { id: inner.bytes()?, index: read_u32(&mut inner, "input index")? };
+  cbor::finish(&inner)?;
+  Ok(input)
+}
+
+// `[address, coin]`
+fn read_byron_output(reader: &mut cbor::Reader) -> Result<TxOutput> {
+  reader.tuple(2, "output")?;
+  let address = cbor::raw(reader)?.to_vec();
+  Ok(TxOutput { address: address, value: reader.unsigned_integer()?, has_assets: false })
+}
+
+// `[type, #6.24(bytes .cbor [key, signature])]`
+fn read_byron_witness(reader: &mut cbor::Reader) -> Result<Witness> {
+  reader.tuple(2, "witness")?;
+  let kind = match reader.unsigned_integer()? {
+    0 => WitnessKind::PubKey,
+    1 => WitnessKind::Script,
+    2 => WitnessKind::Redeem,
+    other => return Err(cbor::error(reader, &format!("unknown witness type {}", other)))
+  };
+  let content = cbor::encoded_cbor(reader)?;
+  let mut witness = Witness {
+    kind: kind, public_key: Vec::new(), signature: Vec::new(), chain_code: Vec::new(), attributes: Vec::new()
+  };
+  if kind != WitnessKind::Script {
+    let mut inner = cbor::reader(&content);
+    inner.tuple(2, "witness content")?;
+    witness.public_key = inner.bytes()?;
+    witness.signature = inner.bytes()?;
+    cbor::finish(&inner)?;
+  }
+  Ok(witness)
+}
+
+// `[[inputs, outputs, attributes], witnesses]`
+fn decode_byron(data: &[u8]) -> Result<DecodedTx> {
+  let mut reader = cbor::reader(data);
+  reader.tuple(2, "signed transaction")?;
+
+  let start = cbor::position(&reader);
+  reader.tuple(3, "transaction")?;
+  let inputs = read_array(&mut reader, read_byron_input)?;
+  let outputs = read_array(&mut reader, read_byron_output)?;
+  let attributes = cbor::raw(&mut reader)?;
+  let body = &data[start..cbor::position(&reader)];
+
+  let witnesses = read_array(&mut reader, read_byron_witness)?;
+  cbor::finish(&reader)?;
+
+  Ok(DecodedTx {
+    era: Era::Byron, tx_id: tx_hash(body), size: data.len(), body: body,
+    inputs: inputs, outputs: outputs, attributes: Some(attributes), fee: None, ttl: None, witnesses: witnesses
+  })
+}
+
+fn read_set<'a, T, F>(reader: &mut cbor::Reader<'a>, read: F) -> Result<Vec<T>>
+  where F: FnMut(&mut cbor::Reader<'a>) -> Result<T>
+{
+  if reader.cbor_type()? == Type::Tag && reader.tag()? != TAG_SET {
+    return Err(cbor::error(reader, "expected a set"));
+  }
+  read_array(reader, read)
+}
+
+fn read_shelley_input(reader: &mut cbor::Reader) -> Result<TxInput> {
+  reader.tuple(2, "input")?;
+  Ok(TxInput { id: reader.bytes()?, index: read_u32(reader, "input index")? })
+}
+
+// `coin / [coin, multiasset]`
+fn read_value(reader: &mut cbor::Reader) -> Result<(u64, bool)> {
+  if reader.cbor_type()? == Type::UnsignedInteger {
+    return Ok((reader.unsigned_integer()?, false));
+  }
+  reader.tuple(2, "multi asset value")?;
+  let coin = reader.unsigned_integer()?;
+  cbor::skip(reader)?;
+  Ok((coin, true))
+}
+
+// Legacy `[address, value, ?datum hash]` or post Alonzo `{0: address, 1: value, ...}`
+fn read_shelley_output(reader: &mut cbor::Reader) -> Result<TxOutput> {
+  if reader.cbor_type()? == Type::Map {
+    let length = reader.map()?;
+    let (mut address, mut value) = (None, None);
+    let mut index = 0;
+    while cbor::has_next(reader, length, index)? {
+      match reader.unsigned_integer()? {
+        0 => address = Some(reader.bytes()?),
+        1 => value = Some(read_value(reader)?),
+        _ => cbor::skip(reader)?
+      }
+      index += 1;
+    }
+    let address = address.ok_or_else(|| cbor::error(reader, "output without address"))?;
+    let (value, has_assets) = value.ok_or_else(|| cbor::error(reader, "output without value"))?;
+    return Ok(TxOutput { address: address, value: value, has_assets: has_assets });
+  }
+  let length = reader.array()?;
+  let address = reader.bytes()?;
+  let (value, has_assets) = read_value(reader)?;
+  let mut index = 2;
+  while cbor::has_next(reader, length, index)? {
+    cbor::skip(reader)?;
+    index += 1;
+  }
+  Ok(TxOutput { address: address, value: value, has_assets: has_assets })
+}
+
+fn read_vkey_witness(reader: &mut cbor::Reader) -> Result<Witness> {
+  reader.tuple(2, "vkey witness")?;
+  Ok(Witness {
+    kind: WitnessKind::VKey, public_key: reader.bytes()?, signature: reader.bytes()?,
+    chain_code: Vec::new(), attributes: Vec::new()
+  })
+}
+
+// `[vkey, signature, chain code, attributes]`
+fn read_bootstrap_witness(reader: &mut cbor::Reader) -> Result<Witness> {
+  reader.tuple(4, "bootstrap witness")?;
+  Ok(Witness {
+    kind: WitnessKind::Bootstrap, public_key: reader.bytes()?, signature: reader.bytes()?,
+    chain_code: reader.bytes()?, attributes: reader.bytes()?
+  })
+}
+
+// `[body, witnesses, auxiliary data]` or `[body, witnesses, is_valid, auxiliary data]`
+fn decode_shelley(data: &[u8]) -> Result<DecodedTx> {
+  let mut reader = cbor::reader(data);
+  let length = match reader.array()? {
+    Len::Len(length) if length == 3 || length == 4 => length,
+    _ => return Err(cbor::error(&reader, "expected a signed transaction of 3 or 4 items"))
+  };
+
+  let start = cbor::position(&reader);
+  let (mut inputs, mut outputs, mut fee, mut ttl) = (Vec::new(), Vec::new(), None, None);
+  let fields = reader.map()?;
+  let mut index = 0;
+  while cbor::has_next(&mut reader, fields, index)? {
+    match reader.unsigned_integer()? {
+      BODY_INPUTS => inputs = read_set(&mut reader, read_shelley_input)?,
+      BODY_OUTPUTS => outputs = read_array(&mut reader, read_shelley_output)?,
+      BODY_FEE => fee = Some(reader.unsigned_integer()?),
+      BODY_TTL => ttl = Some(reader.unsigned_integer()?),
+      _ => cbor::skip(&mut reader)?
+    }
+    index += 1;
+  }
+  let body = &data[start..cbor::position(&reader)];
+
+  let mut witnesses = Vec::new();
+  let fields = reader.map()?;
+  let mut index = 0;
+  while cbor::has_next(&mut reader, fields, index)? {
+    match reader.unsigned_integer()? {
+      WITNESS_VKEYS => witnesses.extend(read_set(&mut reader, read_vkey_witness)?),
+      WITNESS_BOOTSTRAP => witnesses.extend(read_set(&mut reader, read_bootstrap_witness)?),
+      _ => cbor::skip(&mut reader)?
+    }
+    index += 1;
+  }
+  for _ in 2..length {
+    cbor::skip(&mut reader)?;
+  }
+  cbor::finish(&reader)?;
+
+  Ok(DecodedTx {
+    era: Era::Shelley, tx_id: tx_hash(body), size: data.len(), body: body,
+    inputs: inputs, outputs: outputs, attributes: None, fee: fee, ttl: ttl, witnesses: witnesses
+  })
+}
+
+// Byron signed transactions start with the `Tx` array, Shelley ones with the body map
+pub fn decode(data: &[u8]) -> Result<DecodedTx> {
+  let mut reader = cbor::reader(data);
+  reader.array()?;
+  match reader.cbor_type()? {
+    Type::Array => decode_byron(data),
+    Type::Map => decode_shelley(data),
+    _ => Err(cbor::error(&reader, "unknown transaction format"))
+  }
+}
+
+// Text form of a binary output address
+pub fn format_address(address: &[u8]) -> Result<String> {
+  match address.first() {
+    Some(header) if header >> 4 == 8 => Ok(base58::encode(address)),
+    _ => ShelleyAddress::from_bytes(address)?.to_bech32()
+  }
+}
+
+impl<'a> DecodedTx<'a> {
+  fn to_js<'b, C: Context<'b>>(&self, cx: &mut C) -> JsResult<'b, JsObject> {
+    let object = cx.empty_object();
+    let era = cx.string(if self.era == Era::Byron { "byron" } else { "shelley" });
+    object.set(cx, "era", era)?;
+    let tx_id = cx.string(hex::encode(&self.tx_id));
+    object.set(cx, "tx_id", tx_id)?;
+    let size = cx.number(self.size as f64);
+    object.set(cx, "size", size)?;
+
+    let inputs = JsArray::new(cx, self.inputs.len() as u32);
+    for (position, input) in self.inputs.iter().enumerate() {
+      let input_obj = cx.empty_object();
+      let id = cx.string(hex::encode(&input.id));
+      input_obj.set(cx, "id", id)?;
+      let index = cx.number(input.index as f64);
+      input_obj.set(cx, "index", index)?;
+      inputs.set(cx, position as u32, input_obj)?;
+    }
+    object.set(cx, "inputs", inputs)?;
+
+    let outputs = JsArray::new(cx, self.outputs.len() as u32);
+    for (position, output) in self.outputs.iter().enumerate() {
+      let output_obj = cx.empty_object();
+      let address = format_address(&output.address).or_throw(cx)?;
+      let address = cx.string(address);
+      output_obj.set(cx, "address", address)?;
+      set_coin(cx, output_obj, "value", output.value)?;
+      if output.has_assets {
+        let has_assets = cx.boolean(true);
+        output_obj.set(cx, "has_assets", has_assets)?;
+      }
+      outputs.set(cx, position as u32, output_obj)?;
+    }
+    object.set(cx, "outputs", outputs)?;
+
+    if let Some(attributes) = self.attributes {
+      set_bytes(cx, object, "attributes", attributes)?;
+    }
+    if let Some(fee) = self.fee {
+      set_coin(cx, object, "fee", fee)?;
+    }
+    if let Some(ttl) = self.ttl {
+      let ttl = cx.number(ttl as f64);
+      object.set(cx, "ttl", ttl)?;
+    }
+
+    let witnesses = JsArray::new(cx, self.witnesses.len() as u32);
+    for (position, witness) in self.witnesses.iter().enumerate() {
+      let witness_obj = cx.empty_object();
+      let kind = cx.string(witness.kind.name());
+      witness_obj.set(cx, "type", kind)?;
+      if witness.kind != WitnessKind::Script {
+        set_bytes(cx, witness_obj, "public_key", &witness.public_key)?;
+        set_bytes(cx, witness_obj, "signature", &witness.signature)?;
+      }
+      if witness.kind == WitnessKind::Bootstrap {
+        set_bytes(cx, witness_obj, "chain_code", &witness.chain_code)?;
+        set_bytes(cx, witness_obj, "attributes", &witness.attributes)?;
+      }
+      witnesses.set(cx, position as u32, witness_obj)?;
+    }
+    object.set(cx, "witnesses", witnesses)?;
+    Ok(object)
+  }
+}
+
+// Params: tx: Buffer (signed transaction CBOR)
+pub fn decode_transaction(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let tx = cx.argument::<JsBuffer>(0)?;
+
+  let data = {
+    let guard = cx.lock();
+    let tx_buf = tx.borrow(&guard);
+    tx_buf.as_slice::<u8>().to_vec()
+  };
+  let decoded = handle_exception(|| decode(&data)).or_throw(&mut cx)?;
+  decoded.to_js(&mut cx)
+}
+
+pub struct InputCheck {
+  pub witness_present: bool,
+  pub signature_valid: bool,
+  pub address_matches: bool
+}
+
+impl InputCheck {
+  fn is_valid(&self) -> bool {
+    self.witness_present && self.signature_valid && self.address_matches
+  }
+}
+
+// Malformed keys or signatures fail the check instead of the whole verification
+fn signature_matches(public_key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+  public_key.len() >= PUBLIC_KEY_SIZE && verify_signature(public_key, data, signature).unwrap_or(false)
+}
+
+// `tag | protocol magic | tx id`, the magic and the id being CBOR encoded
+pub fn byron_signed_data(tag: u8, protocol_magic: u32, tx_id: &[u8]) -> Vec<u8> {
+  let mut writer = cbor::Writer::new();
+  writer.raw(&[tag]).unsigned(protocol_magic as u64).bytes(tx_id);
+  writer.into_bytes()
+}
+
+// Byron witnesses are in the order of the inputs they unlock
+fn check_byron_input(tx: &DecodedTx, position: usize, address: &[u8], protocol_magic: u32) -> InputCheck {
+  let witness = match tx.witnesses.get(position) {
+    Some(witness) if witness.kind != WitnessKind::Script => witness,
+    _ => return InputCheck { witness_present: false, signature_valid: false, address_matches: false }
+  };
+  let (tag, addr_type) = if witness.kind == WitnessKind::Redeem {
+    (SIGN_REDEEM_TX, ByronAddressType::Redeem)
+  } else {
+    (SIGN_TX, ByronAddressType::PubKey)
+  };
+  let data = byron_signed_data(tag, protocol_magic, &tx.tx_id);
+  let address_matches = match decode_byron(address) {
+    Ok(decoded) => decoded.addr_type == addr_type
+      && decoded.root == byron_root(addr_type, &witness.public_key, &decoded.attributes),
+    Err(_) => false
+  };
+  InputCheck {
+    witness_present: true,
+    signature_valid: signature_matches(&witness.public_key, &data, &witness.signature),
+    address_matches: address_matches
+  }
+}
+
+// Shelley witnesses are unordered: the one whose key hashes to the address credential
+// (or to the Byron address root for bootstrap witnesses) unlocks the input
+fn check_shelley_input(tx: &DecodedTx, address: &[u8]) -> InputCheck {
+  let witness = match address.first() {
+    Some(header) if header >> 4 == 8 => decode_byron(address).ok().and_then(|decoded| {
+      tx.witnesses.iter().filter(|witness| witness.kind == WitnessKind::Bootstrap).find(|witness| {
+        let mut xpub = witness.public_key.clone();
+        xpub.extend_from_slice(&witness.chain_code);
+        xpub.len() == hdwallet::XPUB_SIZE
+          && decoded.root == byron_root(ByronAddressType::PubKey, &xpub, &witness.attributes)
+      })
+    }),
+    _ => match ShelleyAddress::from_bytes(address) {
+      Ok(ShelleyAddress::Base { ref payment, .. }) | Ok(ShelleyAddress::Pointer { ref payment, .. }) |
+      Ok(ShelleyAddress::Enterprise { ref payment, .. }) if payment.kind == CredentialKind::Key => {
+        tx.witnesses.iter().filter(|witness| witness.kind == WitnessKind::VKey).find(|witness| {
+          witness.public_key.len() == PUBLIC_KEY_SIZE && shelley::key_hash(&witness.public_key) == payment.hash
+        })
+      },
+      _ => None
+    }
+  };
+  match witness {
+    Some(witness) => InputCheck {
+      witness_present: true,
+      signature_valid: signature_matches(&witness.public_key, &tx.tx_id, &witness.signature),
+      address_matches: true
+    },
+    None => InputCheck { witness_present: false, signature_valid: false, address_matches: false }
+  }
+}
+
+// Checks every input of `tx` is unlocked by a valid witness of the key owning its
+// resolved output. `protocol_magic` is part of the data signed by Byron witnesses.
+pub fn verify(tx: &DecodedTx, utxos: &[Vec<u8>], protocol_magic: u32) -> Result<Vec<InputCheck>> {
+  if utxos.len() != tx.inputs.len() {
+    return Err(Error::InvalidArgument(format!(
+      "Expected {} resolved inputs, got {}", tx.inputs.len(), utxos.len()
+    )));
+  }
+  Ok(utxos.iter().enumerate().map(|(position, address)| match tx.era {
+    Era::Byron => check_byron_input(tx, position, address, protocol_magic),
+    Era::Shelley => check_shelley_input(tx, address)
+  }).collect())
+}
+
+// Params: tx: Buffer, utxos: JSON string of Array<{address, value}> in input order,
+// protocol_magic: Number
+pub fn verify_transaction(mut cx: FunctionContext) -> JsResult<JsObject> {
+  let tx = cx.argument::<JsBuffer>(0)?;
+  let utxos = cx.argument::<JsString>(1)?.value();
+  let protocol_magic = cx.argument::<JsNumber>(2)?.value();
+
+  let data = {
+    let guard = cx.lock();
+    let tx_buf = tx.borrow(&guard);
+    tx_buf.as_slice::<u8>().to_vec()
+  };
+  let (decoded, checks, input_total) = handle_exception(|| {
+    let protocol_magic = u32_argument(protocol_magic, "protocol magic")?;
+    let utxos = parse_request::<Vec<OutputRequest>>(&utxos)?;
+    let values = utxos.iter().map(|utxo| parse_coin(&utxo.value)).collect::<Result<Vec<_>>>()?;
+    let input_total = sum_coins(values.into_iter())?;
+    let addresses = utxos.iter().map(|utxo| address_bytes(&utxo.address)).collect::<Result<Vec<_>>>()?;
+    let decoded = decode(&data)?;
+    let checks = verify(&decoded, &addresses, protocol_magic)?;
+    Ok((decoded, checks, input_total))
+  }).or_throw(&mut cx)?;
+
+  let object = cx.empty_object();
+  let tx_id = cx.string(hex::encode(&decoded.tx_id));
+  object.set(&mut cx, "tx_id", tx_id)?;
+  let valid = cx.boolean(checks.iter().all(InputCheck::is_valid));
+  object.set(&mut cx, "valid", valid)?;
+  set_coin(&mut cx, object, "input_total", input_total)?;
+
+  let inputs = JsArray::new(&mut cx, checks.len() as u32);
+  for (position, (input, check)) in decoded.inputs.iter().zip(checks.iter()).enumerate() {
+    let input_obj = cx.empty_object();
+    let id = cx.string(hex::encode(&input.id));
+    input_obj.set(&mut cx, "id", id)?;
+    let index = cx.number(input.index as f64);
+    input_obj.set(&mut cx, "index", index)?;
+    let witness_present = cx.boolean(check.witness_present);
+    input_obj.set(&mut cx, "witness_present", witness_present)?;
+    let signature_valid = cx.boolean(check.signature_valid);
+    input_obj.set(&mut cx, "signature_valid", signature_valid)?;
+    let address_matches = cx.boolean(check.address_matches);
+    input_obj.set(&mut cx, "address_matches", address_matches)?;
+    inputs.set(&mut cx, position as u32, input_obj)?;
+  }
+  object.set(&mut cx, "inputs", inputs)?;
+  Ok(object)
+}
@@ -2,203 +2,314 @@ use neon::prelude::*;
 use exception::*;
 use buffer::*;
 use wallet_wasm;
+use task::JsonTask;
+use json::*;
 use std::str;
-use cardano::util::{base58, hex};
+use serde::de::IgnoredAny;
+use cardano::util::hex;
+use address::decode_base58;
+use cardano::hdwallet;
 use super::MAX_OUTPUT_SIZE;
 
 // Params: xprv: Buffer
 pub fn from_master_key(mut cx: FunctionContext) -> JsResult<JsString> {
   let xprv = cx.argument::<JsBuffer>(0)?;
 
-  let mut output_data = [0 as u8; MAX_OUTPUT_SIZE];
-  let output = MutBufferPtr::from(&mut output_data);
+  let mut output = GuardedBuffer::new(MAX_OUTPUT_SIZE);
+  let output_ptr = output.as_mut_ptr();
   {
     let guard = cx.lock();
     let xprv_buf: BufferPtr = xprv.borrow(&guard).into();
 
     handle_exception(|| {
-      let rsz = wallet_wasm::xwallet_from_master_key(xprv_buf.ptr, output.ptr);
-      if rsz <= 0 { panic!("Response {} <= 0", rsz); }
-      if (rsz as usize) > MAX_OUTPUT_SIZE { panic!("Response {} >= {}", rsz, MAX_OUTPUT_SIZE) }
-      output.as_sized_slice(rsz as usize)
+      if xprv_buf.size != hdwallet::XPRV_SIZE {
+        return Err(Error::InvalidLength { field: "XPrv", actual: xprv_buf.size, expected: hdwallet::XPRV_SIZE });
+      }
+      Ok(wallet_wasm::xwallet_from_master_key(xprv_buf.ptr, output_ptr))
     })
-  }.map(|output| {
+  }.and_then(|rsz| output.response(rsz)).map(|output| {
     unsafe { str::from_utf8_unchecked(output) }
   })
   .and_then(|string| {
-    cx.try_string(string).map_err(|_| String::from("Can't create JS string"))
+    cx.try_string(string).map_err(|_| Error::Native(String::from("Can't create JS string")))
   }).or_throw(&mut cx)
 }
 
 // Params: mnemonic: "String"
 pub fn from_daedalus_mnemonic(mut cx: FunctionContext) -> JsResult<JsString> {
-  let mnemonic = cx.argument::<JsString>(0)?.value();
-  
-  let mut output_data = [0 as u8; MAX_OUTPUT_SIZE];
-  let output = MutBufferPtr::from(&mut output_data);
-  
-  handle_exception(|| {
-    let mnemonic_ptr: &[u8] = mnemonic.as_bytes();
-    
-    let rsz = wallet_wasm::xwallet_create_daedalus_mnemonic(
-      mnemonic_ptr.as_ptr(), mnemonic_ptr.len(), output.ptr
-    );
-
-    if rsz <= 0 { panic!("Response {} <= 0", rsz); }
-    if (rsz as usize) > MAX_OUTPUT_SIZE { panic!("Response {} >= {}", rsz, MAX_OUTPUT_SIZE) }
+  let mnemonic = SecureString::from(cx.argument::<JsString>(0)?.value());
 
-    output.as_sized_slice(rsz as usize)
-  }).map(|output| {
-    unsafe { str::from_utf8_unchecked(output) }
-  })
-  .and_then(|string| {
-    cx.try_string(string).map_err(|_| String::from("Can't create JS string"))
-  }).or_throw(&mut cx)
+  call_json(wallet_wasm::xwallet_create_daedalus_mnemonic, &mnemonic, MAX_OUTPUT_SIZE)
+    .and_then(|response| {
+      cx.try_string(&*response).map_err(|_| Error::Native(String::from("Can't create JS string")))
+    }).or_throw(&mut cx)
 }
 
 // Params: params: JSONString
 pub fn new_account(mut cx: FunctionContext) -> JsResult<JsString> {
-  let params = cx.argument::<JsString>(0)?.value();
-  
-  let mut output_data = [0 as u8; MAX_OUTPUT_SIZE];
-  let output = MutBufferPtr::from(&mut output_data);
-  
-  handle_exception(|| {
-    let params_ptr: &[u8] = params.as_bytes();
-    
-    let rsz = wallet_wasm::xwallet_account(
-      params_ptr.as_ptr(), params_ptr.len(), output.ptr
-    );
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
 
-    if rsz <= 0 { panic!("Response {} <= 0", rsz); }
-    if (rsz as usize) > MAX_OUTPUT_SIZE { panic!("Response {} >= {}", rsz, MAX_OUTPUT_SIZE) }
+  call_json(wallet_wasm::xwallet_account, &params, MAX_OUTPUT_SIZE)
+    .and_then(|response| {
+      cx.try_string(&*response).map_err(|_| Error::Native(String::from("Can't create JS string")))
+    }).or_throw(&mut cx)
+}
 
-    output.as_sized_slice(rsz as usize)
-  }).map(|output| {
-    unsafe { str::from_utf8_unchecked(output) }
-  })
-  .and_then(|string| {
-    cx.try_string(string).map_err(|_| String::from("Can't create JS string"))
-  }).or_throw(&mut cx)
+// wallet_wasm has no way to report the size of a response before writing it, so the
+// capacities below are upper bounds built from the CBOR layout of what it encodes.
+// Anything of a fixed size (JSON keys, fee, transaction headers) fits in MAX_OUTPUT_SIZE.
+
+// Byron address: [#6.24(bytes [root 28 bytes, attributes, type]), crc]
+// Headers and root: 1 + 2 + 2 + 1 + 30 + 1 + 1 = 38, crc: 5,
+// HD payload attribute of a two index path: 1 + 2 + 2 + 11 + 16 (Poly1305 tag) = 32,
+// network magic attribute: 1 + 2 + 5 = 8
+const MAX_BYRON_ADDRESS_SIZE: usize = 38 + 5 + 32 + 8;
+// JSON size of one generated address: base58 grows data by log(256) / log(58) < 1.38,
+// rounded up, plus the quotes and comma
+const ADDRESS_JSON_SIZE: usize = MAX_BYRON_ADDRESS_SIZE * 138 / 100 + 1 + 3;
+// Signed Byron input: TxIn [0, #6.24(bytes [id 32 bytes, index])] 1 + 1 + 2 + 2 + 1 + 34 + 5 = 46
+// and witness [0, #6.24(bytes [xpub 64 bytes, signature 64 bytes])] 1 + 1 + 2 + 2 + 1 + 66 + 66 = 139
+const TX_INPUT_SIZE: usize = 46 + 139;
+// Byron output besides the address bytes: array header and coin
+const TX_OUTPUT_SIZE: usize = 1 + 9;
+// `cbor_encoded_tx` is a JSON array, every byte takes at most 4 chars ("255,")
+const TX_JSON_BYTE_SIZE: usize = 4;
+
+#[derive(Deserialize)]
+struct AddressesRequest {
+  indices: Vec<IgnoredAny>
+}
+
+#[derive(Deserialize)]
+struct OutputRequest {
+  address: String
+}
+
+#[derive(Deserialize)]
+struct SpendRequest {
+  inputs: Vec<IgnoredAny>,
+  outputs: Vec<OutputRequest>,
+  change_addr: String
+}
+
+#[derive(Deserialize)]
+struct MoveRequest {
+  inputs: Vec<IgnoredAny>,
+  output: String
+}
+
+// Upper bound of a transaction response. Base58 addresses are
+// always longer than their binary form, so they bound the output size.
+fn transaction_capacity(inputs: usize, addresses: &[&str]) -> usize {
+  let outputs: usize = addresses.iter().map(|address| address.len() + TX_OUTPUT_SIZE).sum();
+  (inputs * TX_INPUT_SIZE + outputs) * TX_JSON_BYTE_SIZE + MAX_OUTPUT_SIZE
+}
+
+fn addresses_capacity(params: &str, alen: f64) -> Result<usize> {
+  let request: AddressesRequest = parse_request(params)?;
+  check_count(alen, request.indices.len(), "address")?;
+  Ok(request.indices.len() * ADDRESS_JSON_SIZE + MAX_OUTPUT_SIZE)
+}
+
+fn spend_capacity(params: &str, ilen: f64, olen: f64) -> Result<usize> {
+  let request: SpendRequest = parse_request(params)?;
+  check_count(ilen, request.inputs.len(), "input")?;
+  check_count(olen, request.outputs.len(), "output")?;
+
+  let mut addresses: Vec<&str> = request.outputs.iter().map(|output| output.address.as_str()).collect();
+  addresses.push(&request.change_addr);
+  Ok(transaction_capacity(request.inputs.len(), &addresses))
+}
+
+fn move_capacity(params: &str, ilen: f64) -> Result<usize> {
+  let request: MoveRequest = parse_request(params)?;
+  check_count(ilen, request.inputs.len(), "input")?;
+  Ok(transaction_capacity(request.inputs.len(), &[&request.output]))
 }
 
 // Params: params: JSONString, alen: Number
 pub fn generate_addresses(mut cx: FunctionContext) -> JsResult<JsString> {
-  let params = cx.argument::<JsString>(0)?.value();
-  let alen = cx.argument::<JsNumber>(1)?.value() as u32 as usize;
-  
-  let output_size = 131 * alen + 2; // (128 + 3 for meta) per addr + 2;
-  let mut output_data: Vec<u8> = Vec::new();
-  output_data.resize(output_size, 0);
-
-  let output = MutBufferPtr::from(&mut output_data);
-  
-  handle_exception(|| {
-    let params_ptr: &[u8] = params.as_bytes();
-    
-    let rsz = wallet_wasm::xwallet_addresses(
-      params_ptr.as_ptr(), params_ptr.len(), output.ptr
-    );
-
-    if rsz <= 0 { panic!("Response {} <= 0", rsz); }
-    if (rsz as usize) > output_size { panic!("Response {} >= {}", rsz, output_size) }
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let alen = cx.argument::<JsNumber>(1)?.value();
 
-    output.as_sized_slice(rsz as usize)
-  }).map(|output| {
-    unsafe { str::from_utf8_unchecked(output) }
-  })
-  .and_then(|string| {
-    cx.try_string(string).map_err(|_| String::from("Can't create JS string"))
-  }).or_throw(&mut cx)
+  addresses_capacity(&params, alen)
+    .and_then(|capacity| call_json(wallet_wasm::xwallet_addresses, &params, capacity))
+    .and_then(|response| {
+      cx.try_string(&*response).map_err(|_| Error::Native(String::from("Can't create JS string")))
+    }).or_throw(&mut cx)
 }
 
 // Params: address: String
 pub fn check_address(mut cx: FunctionContext) -> JsResult<JsString> {
   let address = cx.argument::<JsString>(0)?.value();
-  
-  let mut output_data = [0 as u8; MAX_OUTPUT_SIZE];
-  let output = MutBufferPtr::from(&mut output_data);
-  
-  handle_exception(|| {
-    let decoded = base58::decode(&address).expect("Couldn't decode base58");
-    let fixed_address = format!("\"{}\"", hex::encode(&decoded));
-    let address_ptr: &[u8] = fixed_address.as_bytes();
-    
-    let rsz = wallet_wasm::xwallet_checkaddress(
-      address_ptr.as_ptr(), address_ptr.len(), output.ptr
-    );
 
-    if rsz <= 0 { panic!("Response {} <= 0", rsz); }
-    if (rsz as usize) > MAX_OUTPUT_SIZE { panic!("Response {} >= {}", rsz, MAX_OUTPUT_SIZE) }
-
-    output.as_sized_slice(rsz as usize)
-  }).map(|output| {
-    unsafe { str::from_utf8_unchecked(output) }
-  })
-  .and_then(|string| {
-    cx.try_string(string).map_err(|_| String::from("Can't create JS string"))
-  }).or_throw(&mut cx)
+  decode_base58(&address)
+    .and_then(|decoded| {
+      let fixed_address = format!("\"{}\"", hex::encode(&decoded));
+      call_json(wallet_wasm::xwallet_checkaddress, &fixed_address, MAX_OUTPUT_SIZE)
+    })
+    .and_then(|response| {
+      cx.try_string(&*response).map_err(|_| Error::Native(String::from("Can't create JS string")))
+    }).or_throw(&mut cx)
 }
 
 // Params: params: JSONString, ilen: Number, olen: Number
 pub fn spend(mut cx: FunctionContext) -> JsResult<JsString> {
-  let params = cx.argument::<JsString>(0)?.value();
-  let ilen = cx.argument::<JsNumber>(1)?.value() as u32 as usize;
-  let olen = cx.argument::<JsNumber>(2)?.value() as u32 as usize;
-  
-  let output_size = (ilen + olen + 1) * 65536 + 1024;
-  let mut output_data: Vec<u8> = Vec::new();
-  output_data.resize(output_size, 0);
-
-  let output = MutBufferPtr::from(&mut output_data);
-  
-  handle_exception(|| {
-    let params_ptr: &[u8] = params.as_bytes();
-    
-    let rsz = wallet_wasm::xwallet_spend(
-      params_ptr.as_ptr(), params_ptr.len(), output.ptr
-    );
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let ilen = cx.argument::<JsNumber>(1)?.value();
+  let olen = cx.argument::<JsNumber>(2)?.value();
 
-    if rsz <= 0 { panic!("Response {} <= 0", rsz); }
-    if (rsz as usize) > output_size { panic!("Response {} >= {}", rsz, output_size) }
-
-    output.as_sized_slice(rsz as usize)
-  }).map(|output| {
-    unsafe { str::from_utf8_unchecked(output) }
-  })
-  .and_then(|string| {
-    cx.try_string(string).map_err(|_| String::from("Can't create JS string"))
-  }).or_throw(&mut cx)
+  spend_capacity(&params, ilen, olen)
+    .and_then(|capacity| call_json(wallet_wasm::xwallet_spend, &params, capacity))
+    .and_then(|response| {
+      cx.try_string(&*response).map_err(|_| Error::Native(String::from("Can't create JS string")))
+    }).or_throw(&mut cx)
 }
 
 // Params: params: JSONString, ilen: Number
 pub fn move_func(mut cx: FunctionContext) -> JsResult<JsString> {
-  let params = cx.argument::<JsString>(0)?.value();
-  let ilen = cx.argument::<JsNumber>(1)?.value() as u32 as usize;
-  
-  let output_size = (ilen + 1) * 65536 + 1024;
-  let mut output_data: Vec<u8> = Vec::new();
-  output_data.resize(output_size, 0);
-
-  let output = MutBufferPtr::from(&mut output_data);
-  
-  handle_exception(|| {
-    let params_ptr: &[u8] = params.as_bytes();
-    
-    let rsz = wallet_wasm::xwallet_move(
-      params_ptr.as_ptr(), params_ptr.len(), output.ptr
-    );
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let ilen = cx.argument::<JsNumber>(1)?.value();
 
-    if rsz <= 0 { panic!("Response {} <= 0", rsz); }
-    if (rsz as usize) > output_size { panic!("Response {} >= {}", rsz, output_size) }
+  move_capacity(&params, ilen)
+    .and_then(|capacity| call_json(wallet_wasm::xwallet_move, &params, capacity))
+    .and_then(|response| {
+      cx.try_string(&*response).map_err(|_| Error::Native(String::from("Can't create JS string")))
+    }).or_throw(&mut cx)
+}
 
-    output.as_sized_slice(rsz as usize)
-  }).map(|output| {
-    unsafe { str::from_utf8_unchecked(output) }
-  })
-  .and_then(|string| {
-    cx.try_string(string).map_err(|_| String::from("Can't create JS string"))
-  }).or_throw(&mut cx)
+// Params: params: JSONString, alen: Number, callback: Function
+pub fn generate_addresses_async(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let alen = cx.argument::<JsNumber>(1)?.value();
+  let callback = cx.argument::<JsFunction>(2)?;
+
+  let capacity = addresses_capacity(&params, alen).or_throw(&mut cx)?;
+  JsonTask::new(wallet_wasm::xwallet_addresses, params, capacity).schedule(callback);
+
+  Ok(cx.undefined())
 }
 
+// Params: params: JSONString, ilen: Number, olen: Number, callback: Function
+pub fn spend_async(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let ilen = cx.argument::<JsNumber>(1)?.value();
+  let olen = cx.argument::<JsNumber>(2)?.value();
+  let callback = cx.argument::<JsFunction>(3)?;
+
+  let capacity = spend_capacity(&params, ilen, olen).or_throw(&mut cx)?;
+  JsonTask::new(wallet_wasm::xwallet_spend, params, capacity).schedule(callback);
 
+  Ok(cx.undefined())
+}
+
+// Params: params: JSONString, ilen: Number, callback: Function
+pub fn move_async(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+  let params = SecureString::from(cx.argument::<JsString>(0)?.value());
+  let ilen = cx.argument::<JsNumber>(1)?.value();
+  let callback = cx.argument::<JsFunction>(2)?;
+
+  let capacity = move_capacity(&params, ilen).or_throw(&mut cx)?;
+  JsonTask::new(wallet_wasm::xwallet_move, params, capacity).schedule(callback);
+
+  Ok(cx.undefined())
+}
+
+// The capacities are checked against the largest responses wallet_wasm can produce
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use address::network_magic;
+  use cardano::address::{AddrType, Attributes, ExtendedAddr, SpendingData};
+  use cardano::hdpayload::{HDKey, Path};
+  use cardano::hdwallet::XPrv;
+  use serde_json::{self, Value};
+
+  const SEED: [u8; hdwallet::SEED_SIZE] = [7; hdwallet::SEED_SIZE];
+  const MNEMONIC: &str = "\"abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about\"";
+  const MAX_SOFT_INDEX: u32 = 0x7fff_ffff;
+  const INPUTS: usize = 64;
+  // The inputs hold every lovelace ever to exist
+  const INPUT_VALUE: u64 = 45_000_000_000_000_000 / INPUTS as u64;
+
+  fn result(response: &[u8]) -> Value {
+    let response: Value = serde_json::from_slice(response).unwrap();
+    assert_eq!(response["failed"], Value::Bool(false), "{}", response);
+    response["result"].clone()
+  }
+
+  fn root_key() -> [u8; hdwallet::XPRV_SIZE] {
+    let mut xprv = [0 as u8; hdwallet::XPRV_SIZE];
+    wallet_wasm::wallet_from_seed(SEED.as_ptr(), xprv.as_mut_ptr());
+    xprv
+  }
+
+  fn wallet() -> Value {
+    let xprv = root_key();
+    let mut output = GuardedBuffer::new(MAX_OUTPUT_SIZE);
+    let rsz = wallet_wasm::xwallet_from_master_key(xprv.as_ptr(), output.as_mut_ptr());
+    result(output.response(rsz).unwrap())
+  }
+
+  fn max_addresses(wallet: &Value, count: usize) -> Vec<String> {
+    let params = format!("{{\"wallet\":{},\"account\":{}}}", wallet, MAX_SOFT_INDEX);
+    let account = result(call_json(wallet_wasm::xwallet_account, &params, MAX_OUTPUT_SIZE).unwrap().as_bytes());
+    let params = format!(
+      "{{\"account\":{},\"address_type\":\"Internal\",\"indices\":{:?},\"protocol_magic\":{}}}",
+      account, vec![MAX_SOFT_INDEX; count], u32::max_value()
+    );
+    let capacity = addresses_capacity(&params, count as f64).unwrap();
+    let response = call_json(wallet_wasm::xwallet_addresses, &params, capacity).unwrap();
+    serde_json::from_value(result(response.as_bytes())).unwrap()
+  }
+
+  #[test]
+  fn address_capacity_covers_hd_payloads() {
+    let root = XPrv::from_slice_verified(&root_key()).unwrap();
+    let key = root.derive(hdwallet::DerivationScheme::V1, u32::max_value());
+    let payload = HDKey::new(&root.public()).encrypt_path(&Path::new(vec![u32::max_value(), u32::max_value()]));
+    let attributes = Attributes::new_bootstrap_era(Some(payload), network_magic(u32::max_value()));
+    let address = ExtendedAddr::new(AddrType::ATPubKey, SpendingData::PubKeyASD(key.public()), attributes);
+    assert!(format!("{}", address).len() + 3 <= ADDRESS_JSON_SIZE);
+  }
+
+  #[test]
+  fn addresses_fit_their_capacity() {
+    for address in max_addresses(&wallet(), INPUTS) {
+      assert!(address.len() + 3 <= ADDRESS_JSON_SIZE);
+    }
+  }
+
+  #[test]
+  fn spend_fits_its_capacity() {
+    let wallet = wallet();
+    let addresses = max_addresses(&wallet, 1);
+    let address = &addresses[0];
+    let inputs: Vec<String> = (0..INPUTS).map(|input| format!(
+      "{{\"ptr\":{{\"id\":\"{:064x}\",\"index\":{}}},\"value\":{{\"address\":\"{}\",\"value\":\"{}\"}},\
+       \"addressing\":{{\"account\":{},\"change\":1,\"index\":{}}}}}",
+      input, u32::max_value(), address, INPUT_VALUE, MAX_SOFT_INDEX, MAX_SOFT_INDEX
+    )).collect();
+    // Spends all but 1 ADA, so that every input is selected
+    let value = INPUT_VALUE * INPUTS as u64 - 1_000_000;
+    let params = format!(
+      "{{\"wallet\":{},\"inputs\":[{}],\"outputs\":[{{\"address\":\"{}\",\"value\":\"{}\"}}],\"change_addr\":\"{}\"}}",
+      wallet, inputs.join(","), address, value, address
+    );
+    let capacity = spend_capacity(&params, INPUTS as f64, 1.0).unwrap();
+    call_json(wallet_wasm::xwallet_spend, &params, capacity).unwrap();
+  }
+
+  #[test]
+  fn move_fits_its_capacity() {
+    let wallet = result(call_json(wallet_wasm::xwallet_create_daedalus_mnemonic, MNEMONIC, MAX_OUTPUT_SIZE).unwrap().as_bytes());
+    let address = &max_addresses(&wallet, 1)[0];
+    let inputs: Vec<String> = (0..INPUTS).map(|input| format!(
+      "{{\"ptr\":{{\"id\":\"{:064x}\",\"index\":{}}},\"value\":\"{}\",\"addressing\":[{},{}]}}",
+      input, u32::max_value(), INPUT_VALUE, u32::max_value(), u32::max_value()
+    )).collect();
+    let params = format!("{{\"wallet\":{},\"inputs\":[{}],\"output\":\"{}\"}}", wallet, inputs.join(","), address);
+    let capacity = move_capacity(&params, INPUTS as f64).unwrap();
+    call_json(wallet_wasm::xwallet_move, &params, capacity).unwrap();
+  }
+}
@@ -1,5 +1,48 @@
 import * as rncardano from './rncardano';
 
+export const ErrorCode: {
+  readonly INVALID_LENGTH: 'ERR_INVALID_LENGTH';
+  readonly INVALID_ARGUMENT: 'ERR_INVALID_ARGUMENT';
+  readonly BAD_PASSWORD: 'ERR_BAD_PASSWORD';
+  readonly DECODE: 'ERR_DECODE';
+  readonly INSUFFICIENT_FUNDS: 'ERR_INSUFFICIENT_FUNDS';
+  readonly OUTPUT_TOO_LARGE: 'ERR_OUTPUT_TOO_LARGE';
+  readonly KEY_DESTROYED: 'ERR_KEY_DESTROYED';
+  readonly MNEMONIC_WORD_COUNT: 'ERR_MNEMONIC_WORD_COUNT';
+  readonly MNEMONIC_UNKNOWN_WORD: 'ERR_MNEMONIC_UNKNOWN_WORD';
+  readonly MNEMONIC_CHECKSUM: 'ERR_MNEMONIC_CHECKSUM';
+  readonly TX_TOO_LARGE: 'ERR_TX_TOO_LARGE';
+  readonly INVALID_PATH: 'ERR_INVALID_PATH';
+  readonly HARDENED_PUBLIC_DERIVATION: 'ERR_HARDENED_PUBLIC_DERIVATION';
+  readonly WALLET: 'ERR_WALLET';
+  readonly NATIVE: 'ERR_NATIVE';
+};
+
+export type ErrorCode = typeof ErrorCode[keyof typeof ErrorCode];
+
+// Error thrown by every function of the package.
+export interface CardanoError extends Error {
+  code: ErrorCode;
+  // ERR_INVALID_LENGTH details
+  field?: string;
+  actual?: number;
+  expected?: number;
+  min?: number;
+  // ERR_MNEMONIC_WORD_COUNT, ERR_MNEMONIC_UNKNOWN_WORD and derivation path details
+  words?: number;
+  position?: number;
+  step?: string;
+  // ERR_OUTPUT_TOO_LARGE and ERR_TX_TOO_LARGE details
+  size?: number;
+  max?: number;
+  // ERR_INSUFFICIENT_FUNDS details of native transaction builders, in lovelace
+  available?: string;
+  needed?: string;
+  // ERR_WALLET and ERR_INSUFFICIENT_FUNDS details of the JSON based wallet operations
+  loc?: string;
+  msg?: string;
+}
+
 export namespace HdWallet {
   export type XPrv = Buffer;
   export type XPub = Buffer;
@@ -19,8 +62,227 @@ export namespace HdWallet {
   // Create a derived public key with an index.
   export function derivePublic(xpub: XPub, index: number): XPub;
 
+  // Create a derived private key with a path like "m/44'/1815'/0'/0/0".
+  export function derivePrivatePath(xprv: XPrv, path: string): XPrv;
+
+  // Create a derived public key with a path of soft indices like "0/0".
+  export function derivePublicPath(xpub: XPub, path: string): XPub;
+
   // Sign the given message with the private key.
   export function sign(xprv: XPrv, msg: Buffer): Buffer;
+
+  // Check the signature of the message with the public key.
+  export function verify(xpub: XPub, msg: Buffer, signature: Buffer): boolean;
+
+  // Check the signature of the message with a raw 32 bytes Ed25519 public key.
+  export function verifyPublicKey(publicKey: Buffer, msg: Buffer, signature: Buffer): boolean;
+
+  // Private key held in native memory, its bytes are not exposed unless exported.
+  export class PrivateKey {
+    private constructor();
+
+    // Get a public key for the private one.
+    toPublic(): XPub;
+
+    // Create a derived private key with an index.
+    derive(index: number): PrivateKey;
+
+    // Create a derived private key with a path like "m/44'/1815'/0'/0/0".
+    derivePath(path: string): PrivateKey;
+
+    // Sign the given message with the private key.
+    sign(msg: Buffer): Buffer;
+
+    // Copy the key bytes into a Buffer.
+    export(): XPrv;
+
+    // Check if the key was wiped.
+    isDestroyed(): boolean;
+
+    // Wipe the key from memory. Any later call will throw.
+    destroy(): void;
+  }
+
+  // Generate a native private key from the given entropy and the given password.
+  export function privateKeyFromEnhancedEntropy(entropy: Buffer, password: Buffer): PrivateKey;
+
+  // Create a native private key from the given seed.
+  export function privateKeyFromSeed(seed: Buffer): PrivateKey;
+
+  // Import an eXtended private key into native memory.
+  export function privateKeyFromXPrv(xprv: XPrv): PrivateKey;
+}
+
+export namespace Mnemonic {
+  export type Language = rncardano.Mnemonic.Language;
+
+  export const LANGUAGES: ReadonlyArray<Language>;
+
+  // Generate a BIP39 mnemonic phrase of 12, 15, 18, 21 or 24 words (English by default).
+  export function generate(words: number, language?: Language): string;
+
+  // Check the words and the checksum of the phrase, throws on error.
+  // Returns the language of the phrase, detected when not given.
+  export function validate(phrase: string, language?: Language): Language;
+
+  // Get the entropy of the phrase, usable with HdWallet.fromEnhancedEntropy.
+  export function toEntropy(phrase: string, language?: Language): Buffer;
+
+  // Create a phrase for the given entropy (English by default).
+  export function fromEntropy(entropy: Buffer, language?: Language): string;
+
+  // Get the dictionary words starting with the given prefix (English by default).
+  export function suggest(prefix: string, language?: Language): Array<string>;
+
+  // Get the first language containing every word of the phrase.
+  export function detectLanguage(phrase: string): Language;
+}
+
+export namespace Address {
+  // Child public keys (64 bytes each) and length prefixed base58 addresses, packed back to back.
+  export type PackedRange = { xpubs: Buffer, addresses: Buffer };
+  export type DecodedAddress = {
+    root: Buffer;
+    type: rncardano.Address.ByronAddressType;
+    protocolMagic?: number; // absent on mainnet addresses
+    hasHdPayload: boolean;
+    crc: number;
+    crcValid: boolean;
+  };
+
+  // Derive `count` child public keys of an account chain starting at `start`, with their addresses.
+  // `count` is at most 100000 and the range must stay below the hardened indices.
+  // Large ranges can be split across CPU cores.
+  export function deriveRange(
+    xpub: HdWallet.XPub, type: rncardano.Wallet.AddressType, start: number, count: number,
+    protocolMagic: number, parallel?: boolean
+  ): PackedRange;
+
+  // Derive a range of child public keys and addresses on a background thread.
+  export function deriveRangeAsync(
+    xpub: HdWallet.XPub, type: rncardano.Wallet.AddressType, start: number, count: number,
+    protocolMagic: number, parallel?: boolean
+  ): Promise<PackedRange>;
+
+  // Split the packed addresses of a range.
+  export function unpackAddresses(packed: Buffer): Array<rncardano.Wallet.Address>;
+
+  // Decode a base58 Byron address into its components.
+  export function decode(address: rncardano.Wallet.Address): DecodedAddress;
+
+  // Check the address is a valid address of the network with the given protocol magic.
+  export function validate(address: string, protocolMagic: number): rncardano.Address.Validation;
+}
+
+export namespace Shelley {
+  export const NETWORK_TESTNET: 0;
+  export const NETWORK_MAINNET: 1;
+
+  export type Credential = { type: "key" | "script"; hash: Buffer };
+  export type DecodedAddress = {
+    type: rncardano.Shelley.AddressType;
+    networkId: number;
+    payment?: Credential; // base, pointer and enterprise addresses
+    stake?: Credential; // base and reward addresses
+    pointer?: rncardano.Shelley.Pointer; // pointer addresses
+  };
+
+  // Create the m/1852'/1815'/account' account of the wallet, with its stake key.
+  export function newAccount(wallet: rncardano.Wallet.WalletObj, account: number): rncardano.Shelley.AccountObj;
+
+  // Get the public key of the account at the given chain (role) and index.
+  export function chainKey(account: rncardano.Wallet.AccountObj, role: rncardano.Shelley.Role, index: number): HdWallet.XPub;
+
+  // Create a base address paying to the payment key and delegating with the stake key.
+  export function baseAddress(paymentXPub: HdWallet.XPub, stakeXPub: HdWallet.XPub, networkId: number): rncardano.Shelley.Address;
+
+  // Create a pointer address delegating with the stake key registered at the given pointer.
+  export function pointerAddress(
+    paymentXPub: HdWallet.XPub, pointer: rncardano.Shelley.Pointer, networkId: number
+  ): rncardano.Shelley.Address;
+
+  // Create an enterprise address, without staking rights.
+  export function enterpriseAddress(paymentXPub: HdWallet.XPub, networkId: number): rncardano.Shelley.Address;
+
+  // Create a reward address of the stake key.
+  export function rewardAddress(stakeXPub: HdWallet.XPub, networkId: number): rncardano.Shelley.Address;
+
+  // Decode a bech32 Shelley address into its components.
+  export function decodeAddress(address: rncardano.Shelley.Address): DecodedAddress;
+
+  // Generate base addresses for the given account, staked with its first staking key.
+  export function generateAddresses(
+    account: rncardano.Wallet.AccountObj, type: rncardano.Wallet.AddressType, indices: Array<number>, networkId: number
+  ): Array<rncardano.Shelley.Address>;
+
+  // Generate enterprise addresses for the given account.
+  export function generateEnterpriseAddresses(
+    account: rncardano.Wallet.AccountObj, type: rncardano.Wallet.AddressType, indices: Array<number>, networkId: number
+  ): Array<rncardano.Shelley.Address>;
+
+  export type TransactionObj = {
+    cbor_encoded_tx: Buffer;
+    tx_id: string;
+    fee: string;
+    deposit: string;
+    refund: string;
+    change_used: boolean;
+  };
+
+  // Generate a ready to send, signed, Shelley transaction spending all the inputs.
+  export function spend(
+    wallet: rncardano.Wallet.WalletObj, inputs: Array<rncardano.Shelley.InputObj>,
+    outputs: Array<rncardano.Wallet.OutputObj>, change_addr: string, options: rncardano.Shelley.TransactionOptions
+  ): TransactionObj;
+
+  export type UnsignedTransactionObj = {
+    body: Buffer;
+    paths: Array<string>; // keys which must witness the body, like "m/1852'/1815'/0'/0/3"
+    tx_id: string;
+    fee: string;
+    deposit: string;
+    refund: string;
+    change_used: boolean;
+  };
+
+  // Build the transaction body with the account public key only. Inputs, certificates
+  // and withdrawals must all belong to this account. Byron inputs are not supported.
+  export function buildUnsigned(
+    account: rncardano.Shelley.AccountObj, inputs: Array<rncardano.Shelley.InputObj>,
+    outputs: Array<rncardano.Wallet.OutputObj>, change_addr: string, options: rncardano.Shelley.TransactionOptions
+  ): UnsignedTransactionObj;
+
+  // Witness the body with the keys at the given paths of the root key, returning the signed transaction.
+  export function sign(xprv: HdWallet.XPrv, body: Buffer, paths: Array<string>): Buffer;
+}
+
+export namespace Transaction {
+  export type WitnessObj = {
+    type: rncardano.Transaction.WitnessType;
+    public_key?: Buffer; // XPub for Byron witnesses, Ed25519 key for Shelley ones
+    signature?: Buffer;
+    chain_code?: Buffer; // bootstrap witnesses
+    attributes?: Buffer; // bootstrap witnesses
+  };
+  export type DecodedTransaction = {
+    era: rncardano.Transaction.Era;
+    tx_id: string;
+    size: number;
+    inputs: Array<rncardano.Transaction.InputObj>;
+    outputs: Array<rncardano.Transaction.OutputObj>;
+    attributes?: Buffer; // Byron
+    fee?: string; // Shelley
+    ttl?: number; // Shelley
+    witnesses: Array<WitnessObj>;
+  };
+
+  // Decode a signed Byron or Shelley transaction.
+  export function decode(tx: Buffer): DecodedTransaction;
+
+  // Check the witnesses against the outputs spent by the inputs, given in input order.
+  export function verify(
+    tx: Buffer, utxos: Array<rncardano.Wallet.OutputObj>, config: { protocol_magic: number }
+  ): rncardano.Transaction.Verification;
 }
 
 export namespace Wallet {
@@ -28,6 +290,8 @@ export namespace Wallet {
     cbor_encoded_tx: Buffer;
     change_used: boolean;
     fee: string;
+    tx_id?: string; // built natively
+    selected_inputs?: Array<{ id: string; index: number }>; // built natively, in spending order
   };
 
   // Create a wallet object from the given seed.
@@ -44,34 +308,143 @@ export namespace Wallet {
     account: rncardano.Wallet.AccountObj, type: rncardano.Wallet.AddressType, indices: Array<number>, protocolMagic: number
   ): Array<rncardano.Wallet.Address>;
 
+  // Generate addresses for the given wallet on a background thread.
+  export function generateAddressesAsync(
+    account: rncardano.Wallet.AccountObj, type: rncardano.Wallet.AddressType, indices: Array<number>, protocolMagic: number
+  ): Promise<Array<rncardano.Wallet.Address>>;
+
   // Check if the given base58 string is a valid Cardano Extended Address.
   export function checkAddress(address: rncardano.Wallet.Address): boolean;
 
+  // Generate a daedalus random address, with the encrypted hardened [account, index] (random if not given).
+  export function generateRandomAddress(
+    wallet: rncardano.Wallet.DaedalusWalletObj, addressing?: [number, number]
+  ): { address: rncardano.Wallet.Address, addressing: [number, number] };
+
   // Generate a ready to send, signed, transaction.
   export function spend(
     wallet: rncardano.Wallet.WalletObj, inputs: Array<rncardano.Wallet.SpendInputObj>,
-    outputs: Array<rncardano.Wallet.OutputObj>, change_addr: rncardano.Wallet.Address
+    outputs: Array<rncardano.Wallet.OutputObj>, change_addr: rncardano.Wallet.Address,
+    protocolParams?: rncardano.ProtocolParams, selectionSeed?: number
   ): TransactionObj;
 
+  // Generate a ready to send, signed, transaction on a background thread.
+  export function spendAsync(
+    wallet: rncardano.Wallet.WalletObj, inputs: Array<rncardano.Wallet.SpendInputObj>,
+    outputs: Array<rncardano.Wallet.OutputObj>, change_addr: rncardano.Wallet.Address,
+    protocolParams?: rncardano.ProtocolParams, selectionSeed?: number
+  ): Promise<TransactionObj>;
+
+  // Estimate the fee of the transaction spend would build with the given selection policy.
+  export function estimateFee(
+    inputs: Array<rncardano.Wallet.SpendInputObj>, outputs: Array<rncardano.Wallet.OutputObj>,
+    change_addr: rncardano.Wallet.Address, protocolParams?: rncardano.ProtocolParams,
+    selectionPolicy?: rncardano.Wallet.SelectionPolicy, selectionSeed?: number
+  ): rncardano.Wallet.FeeEstimate;
+
+  export type DrainTransactionObj = TransactionObj & {
+    value: string; // amount received by the output
+  };
+
+  // Send all the inputs to a single address, the fee being deducted from the output.
+  export function drain(
+    wallet: rncardano.Wallet.WalletObj, inputs: Array<rncardano.Wallet.SpendInputObj>,
+    output: rncardano.Wallet.Address, protocolParams?: rncardano.ProtocolParams
+  ): DrainTransactionObj;
+
+  // Send all the inputs to a single address on a background thread.
+  export function drainAsync(
+    wallet: rncardano.Wallet.WalletObj, inputs: Array<rncardano.Wallet.SpendInputObj>,
+    output: rncardano.Wallet.Address, protocolParams?: rncardano.ProtocolParams
+  ): Promise<DrainTransactionObj>;
+
+  export type MigrationObj = {
+    transactions: Array<{
+      cbor_encoded_tx: Buffer; // Shelley transaction with bootstrap witnesses
+      tx_id: string;
+      fee: string;
+      value: string;
+      selected_inputs: Array<{ id: string; index: number }>;
+    }>;
+    skipped_inputs: Array<{ id: string; index: number }>; // not worth the fee of their transaction or too large to spend
+    fee: string;
+    value: string;
+  };
+
+  // Move Byron funds to a Shelley address, in as few transactions as the size limit allows.
+  export function migrate(
+    wallet: rncardano.Wallet.WalletObj, inputs: Array<rncardano.Wallet.MigrationInputObj>,
+    output: rncardano.Shelley.Address, ttl: number, protocolParams?: rncardano.ProtocolParams
+  ): MigrationObj;
+
+  // Move Byron funds to a Shelley address on a background thread.
+  export function migrateAsync(
+    wallet: rncardano.Wallet.WalletObj, inputs: Array<rncardano.Wallet.MigrationInputObj>,
+    output: rncardano.Shelley.Address, ttl: number, protocolParams?: rncardano.ProtocolParams
+  ): Promise<MigrationObj>;
+
   // Move all UTxO to a single address.
   export function move(
     wallet: rncardano.Wallet.DaedalusWalletObj,
     inputs: Array<rncardano.Wallet.MoveInputObj>,
-    output: rncardano.Wallet.Address
+    output: rncardano.Wallet.Address,
+    protocolParams?: rncardano.ProtocolParams
   ): TransactionObj;
+
+  // Move all UTxO to a single address on a background thread.
+  export function moveAsync(
+    wallet: rncardano.Wallet.DaedalusWalletObj,
+    inputs: Array<rncardano.Wallet.MoveInputObj>,
+    output: rncardano.Wallet.Address,
+    protocolParams?: rncardano.ProtocolParams
+  ): Promise<TransactionObj>;
 }
 
 export namespace RandomAddressChecker {
+  // Base58 string or binary address.
+  export type AddressLike = rncardano.Wallet.Address | Buffer;
+
+  // Address checker holding the HD payload key in native memory.
+  export class RandomAddressChecker {
+    private constructor();
+
+    // Keep the addresses of the wallet, with their hardened [account, index].
+    check<A extends AddressLike>(addresses: Array<A>): Array<{ address: A, addressing: [number, number] }>;
+
+    // Get the [account, index] of an address of the wallet, null for other addresses.
+    checkOne(address: AddressLike): [number, number] | null;
+
+    // Keep the addresses of the wallet on a background thread.
+    checkAsync<A extends AddressLike>(
+      addresses: Array<A>, callback: (err: Error | null, found: Array<{ address: A, addressing: [number, number] }>) => void
+    ): void;
+
+    // Check if the key was wiped.
+    isDestroyed(): boolean;
+
+    // Wipe the key from memory. Any later call will throw.
+    destroy(): void;
+  }
+
   // Create a random address checker, this will allow validating.
-  export function newChecker(xprv: HdWallet.XPrv): rncardano.RandomAddressChecker.AddressCheckerObj;
+  // Breaking: returns a native object and the root XPrv must be a Buffer, hex strings used to be accepted.
+  export function newChecker(xprv: HdWallet.XPrv): RandomAddressChecker;
 
   // Create a random address checker from daedalus mnemonics.
-  export function newCheckerFromMnemonics(mnemonics: string): rncardano.RandomAddressChecker.AddressCheckerObj;
+  export function newCheckerFromMnemonics(mnemonics: string): RandomAddressChecker;
+
+  // Deprecated JSON checker of earlier versions, which carries its key.
+  export type LegacyCheckerObj = object;
 
   // Check if the given addresses are valid.
-  export function checkAddresses(
-    checker: rncardano.RandomAddressChecker.AddressCheckerObj, addresses: Array<rncardano.Wallet.Address>
-  ): Array<{ address: rncardano.Wallet.Address, addressing: [number, number] }>;
+  export function checkAddresses<A extends AddressLike>(
+    checker: RandomAddressChecker | LegacyCheckerObj, addresses: Array<A>
+  ): Array<{ address: A, addressing: [number, number] }>;
+
+  // Check if the given addresses are valid on a background thread.
+  export function checkAddressesAsync<A extends AddressLike>(
+    checker: RandomAddressChecker | LegacyCheckerObj, addresses: Array<A>
+  ): Promise<Array<{ address: A, addressing: [number, number] }>>;
 }
 
 export namespace PasswordProtect {
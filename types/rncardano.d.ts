@@ -1,6 +1,17 @@
 declare namespace RNCardano {
   export type HexString = string;
 
+  // Network parameters used to build transactions. Defaults to the mainnet values of the era:
+  // Byron 155381 + 43.946 * size, Shelley 155381 + 44 * size with its deposits and min UTxO.
+  export type ProtocolParams = {
+    fee_constant: number;
+    fee_coefficient: number;
+    max_tx_size: number;
+    min_utxo: number;
+    key_deposit: number;
+    pool_deposit: number;
+  };
+
   export namespace HdWallet {
     export type XPrv = HexString;
     export type XPub = HexString;
@@ -20,17 +31,245 @@ declare namespace RNCardano {
     // Create a derived public key with an index.
     export function derivePublic(xpub: XPub, index: number): Promise<XPub>;
 
+    // Create a derived private key with a path like "m/44'/1815'/0'/0/0".
+    export function derivePrivatePath(xprv: XPrv, path: string): Promise<XPrv>;
+
+    // Create a derived public key with a path of soft indices like "0/0".
+    export function derivePublicPath(xpub: XPub, path: string): Promise<XPub>;
+
     // Sign the given message with the private key.
     export function sign(xprv: XPrv, msg: HexString): Promise<HexString>;
+
+    // Check the signature of the message with the public key.
+    export function verify(xpub: XPub, msg: HexString, signature: HexString): Promise<boolean>;
+
+    // Check the signature of the message with a raw 32 bytes Ed25519 public key.
+    export function verifyPublicKey(publicKey: HexString, msg: HexString, signature: HexString): Promise<boolean>;
+  }
+
+  export namespace Mnemonic {
+    export type Language =
+      "english" | "japanese" | "spanish" | "french" | "italian" | "korean" |
+      "chinese_simplified" | "chinese_traditional";
+
+    export const LANGUAGES: ReadonlyArray<Language>;
+
+    // Generate a BIP39 mnemonic phrase of 12, 15, 18, 21 or 24 words (English by default).
+    export function generate(words: number, language?: Language): Promise<string>;
+
+    // Check the words and the checksum of the phrase, rejects on error.
+    // Resolves with the language of the phrase, detected when not given.
+    export function validate(phrase: string, language?: Language): Promise<Language>;
+
+    // Get the entropy of the phrase, usable with HdWallet.fromEnhancedEntropy.
+    export function toEntropy(phrase: string, language?: Language): Promise<HexString>;
+
+    // Create a phrase for the given entropy (English by default).
+    export function fromEntropy(entropy: HexString, language?: Language): Promise<string>;
+
+    // Get the dictionary words starting with the given prefix (English by default).
+    export function suggest(prefix: string, language?: Language): Promise<Array<string>>;
+
+    // Get the first language containing every word of the phrase.
+    export function detectLanguage(phrase: string): Promise<Language>;
+  }
+
+  export namespace Address {
+    export type ByronAddressType = "pubkey" | "script" | "redeem";
+    export type DecodedAddress = {
+      root: HexString;
+      type: ByronAddressType;
+      protocolMagic?: number; // absent on mainnet addresses
+      hasHdPayload: boolean;
+      crc: number;
+      crcValid: boolean;
+    };
+    export type Verdict = "valid" | "wrong_network" | "bad_checksum" | "unsupported_era" | "malformed";
+    export type Validation = {
+      verdict: Verdict;
+      protocolMagic?: number; // network of a Byron address when it could be decoded
+      networkId?: number; // network of a Shelley address when it could be decoded
+      reason?: string; // why a malformed address couldn't be decoded
+    };
+
+    // Derive `count` child public keys of an account chain starting at `start`, with their addresses.
+    // `count` is at most 100000 and the range must stay below the hardened indices.
+    export function deriveRange(
+      xpub: HdWallet.XPub, type: Wallet.AddressType, start: number, count: number, protocolMagic: number
+    ): Promise<{ xpubs: Array<HdWallet.XPub>, addresses: Array<Wallet.Address> }>;
+
+    // Decode a base58 Byron address into its components.
+    export function decode(address: Wallet.Address): Promise<DecodedAddress>;
+
+    // Check the address is a valid address of the network with the given protocol magic.
+    export function validate(address: string, protocolMagic: number): Promise<Validation>;
+  }
+
+  export namespace Shelley {
+    export const NETWORK_TESTNET: 0;
+    export const NETWORK_MAINNET: 1;
+
+    export type Address = string; // bech32
+    export type AddressType = "base" | "pointer" | "enterprise" | "reward";
+    export type Credential = { type: "key" | "script"; hash: HexString };
+    export type Pointer = { slot: number; txIndex: number; certIndex: number };
+    export type Role = "External" | "Internal" | "Staking";
+    // CIP-1852 account, usable wherever an AccountObj is expected
+    export type AccountObj = Wallet.AccountObj & {
+      purpose: 1852;
+      account: number;
+      stake_key: HdWallet.XPub;
+      stake_key_hash: HexString;
+    };
+
+    // Create the m/1852'/1815'/account' account of the wallet, with its stake key.
+    export function newAccount(wallet: Wallet.WalletObj, account: number): Promise<AccountObj>;
+
+    // Get the public key of the account at the given chain (role) and index.
+    export function chainKey(account: Wallet.AccountObj, role: Role, index: number): Promise<HdWallet.XPub>;
+    export type DecodedAddress = {
+      type: AddressType;
+      networkId: number;
+      payment?: Credential; // base, pointer and enterprise addresses
+      stake?: Credential; // base and reward addresses
+      pointer?: Pointer; // pointer addresses
+    };
+
+    // Create a base address paying to the payment key and delegating with the stake key.
+    export function baseAddress(paymentXPub: HdWallet.XPub, stakeXPub: HdWallet.XPub, networkId: number): Promise<Address>;
+
+    // Create a pointer address delegating with the stake key registered at the given pointer.
+    export function pointerAddress(paymentXPub: HdWallet.XPub, pointer: Pointer, networkId: number): Promise<Address>;
+
+    // Create an enterprise address, without staking rights.
+    export function enterpriseAddress(paymentXPub: HdWallet.XPub, networkId: number): Promise<Address>;
+
+    // Create a reward address of the stake key.
+    export function rewardAddress(stakeXPub: HdWallet.XPub, networkId: number): Promise<Address>;
+
+    // Decode a bech32 Shelley address into its components.
+    export function decodeAddress(address: Address): Promise<DecodedAddress>;
+
+    // Generate base addresses for the given account, staked with its first staking key.
+    export function generateAddresses(
+      account: Wallet.AccountObj, type: Wallet.AddressType, indices: Array<number>, networkId: number
+    ): Promise<Array<Address>>;
+
+    // Generate enterprise addresses for the given account.
+    export function generateEnterpriseAddresses(
+      account: Wallet.AccountObj, type: Wallet.AddressType, indices: Array<number>, networkId: number
+    ): Promise<Array<Address>>;
+
+    export type InputObj = {
+      ptr: { id: string; index: number };
+      value: Wallet.OutputObj;
+      addressing: { account: number; change: number; index: number }; // CIP-1852 path
+    };
+    export type CertificateObj =
+      { type: "stake_registration"; account: number } |
+      { type: "stake_deregistration"; account: number } |
+      { type: "stake_delegation"; account: number; pool: HexString };
+    export type WithdrawalObj = { account: number; value: string };
+    export type TransactionOptions = {
+      ttl: number;
+      network_id: number;
+      certificates?: Array<CertificateObj>;
+      withdrawals?: Array<WithdrawalObj>; // rewards of the account stake key
+      protocol_params?: ProtocolParams;
+    };
+    export type TransactionObj = {
+      cbor_encoded_tx: HexString;
+      tx_id: HexString;
+      fee: string;
+      deposit: string; // stake key deposits paid
+      refund: string; // stake key deposits returned
+      change_used: boolean;
+    };
+
+    // Generate a ready to send, signed, Shelley transaction spending all the inputs.
+    export function spend(
+      wallet: Wallet.WalletObj, inputs: Array<InputObj>, outputs: Array<Wallet.OutputObj>,
+      change_addr: string, options: TransactionOptions
+    ): Promise<TransactionObj>;
+
+    export type UnsignedTransactionObj = {
+      body: HexString;
+      paths: Array<string>; // keys which must witness the body, like "m/1852'/1815'/0'/0/3"
+      tx_id: HexString;
+      fee: string;
+      deposit: string;
+      refund: string;
+      change_used: boolean;
+    };
+
+    // Build the transaction body with the account public key only. Inputs, certificates
+    // and withdrawals must all belong to this account. Byron inputs are not supported.
+    export function buildUnsigned(
+      account: AccountObj, inputs: Array<InputObj>, outputs: Array<Wallet.OutputObj>,
+      change_addr: string, options: TransactionOptions
+    ): Promise<UnsignedTransactionObj>;
+
+    // Witness the body with the keys at the given paths of the root key, returning the signed transaction.
+    export function sign(xprv: HdWallet.XPrv, body: HexString, paths: Array<string>): Promise<HexString>;
+  }
+
+  export namespace Transaction {
+    export type Era = "byron" | "shelley";
+    export type WitnessType = "pubkey" | "script" | "redeem" | "vkey" | "bootstrap";
+    export type InputObj = { id: HexString; index: number };
+    export type OutputObj = {
+      address: string; // base58 for Byron, bech32 for Shelley
+      value: string;
+      has_assets?: boolean;
+    };
+    export type WitnessObj = {
+      type: WitnessType;
+      public_key?: HexString; // XPub for Byron witnesses, Ed25519 key for Shelley ones
+      signature?: HexString;
+      chain_code?: HexString; // bootstrap witnesses
+      attributes?: HexString; // bootstrap witnesses
+    };
+    export type DecodedTransaction = {
+      era: Era;
+      tx_id: HexString;
+      size: number;
+      inputs: Array<InputObj>;
+      outputs: Array<OutputObj>;
+      attributes?: HexString; // Byron
+      fee?: string; // Shelley
+      ttl?: number; // Shelley
+      witnesses: Array<WitnessObj>;
+    };
+
+    // Decode a signed Byron or Shelley transaction.
+    export function decode(tx: HexString): Promise<DecodedTransaction>;
+
+    export type InputCheck = InputObj & {
+      witness_present: boolean;
+      signature_valid: boolean;
+      address_matches: boolean; // the witness key owns the spent address
+    };
+    export type Verification = {
+      tx_id: HexString;
+      valid: boolean; // every input passed all the checks
+      input_total: string;
+      inputs: Array<InputCheck>;
+    };
+
+    // Check the witnesses against the outputs spent by the inputs, given in input order.
+    export function verify(
+      tx: HexString, utxos: Array<Wallet.OutputObj>, config: { protocol_magic: number }
+    ): Promise<Verification>;
   }
 
   export namespace Wallet {
     export type WalletObj = {
       root_cached_key: HdWallet.XPrv;
       derivation_scheme: string;
-      selection_policy: string;
+      selection_policy: SelectionPolicy;
       config: { protocol_magic: number };
     };
+    export type SelectionPolicy = "FirstMatchFirst" | "LargestFirst" | "OldestFirst" | "RandomImprove" | "MinimizeChange";
     export type DaedalusWalletObj = WalletObj;
     export type AccountObj = {
       root_cached_key: HdWallet.XPrv;
@@ -42,6 +281,7 @@ declare namespace RNCardano {
       ptr: { id: string; index: number };
       value: OutputObj;
       addressing: { account: number; change: number; index: number };
+      slot?: number; // creation slot, orders inputs for OldestFirst
     };
     export type MoveInputObj = {
       ptr: { id: string; index: number };
@@ -53,6 +293,8 @@ declare namespace RNCardano {
       cbor_encoded_tx: HexString;
       change_used: boolean;
       fee: string;
+      tx_id?: HexString; // built natively
+      selected_inputs?: Array<{ id: string; index: number }>; // built natively, in spending order
     };
 
     // Create a wallet object from the given seed.
@@ -72,19 +314,78 @@ declare namespace RNCardano {
     // Check if the given base58 string is a valid Cardano Extended Address.
     export function checkAddress(address: Address): Promise<boolean>;
 
+    // Generate a daedalus random address, with the encrypted hardened [account, index] (random if not given).
+    export function generateRandomAddress(
+      wallet: DaedalusWalletObj, addressing?: [number, number]
+    ): Promise<{ address: Address; addressing: [number, number] }>;
+
     // Generate a ready to send, signed, transaction.
     export function spend(
-      wallet: WalletObj, inputs: Array<SpendInputObj>, outputs: Array<OutputObj>, change_addr: Address
+      wallet: WalletObj, inputs: Array<SpendInputObj>, outputs: Array<OutputObj>, change_addr: Address,
+      protocolParams?: ProtocolParams, selectionSeed?: number
     ): Promise<TransactionObj>;
 
+    export type FeeEstimate = {
+      fee: string;
+      tx_size: number;
+      change_used: boolean;
+      selected_inputs: Array<{ id: string; index: number }>;
+    };
+
+    // Estimate the fee of the transaction spend would build with the given selection policy.
+    export function estimateFee(
+      inputs: Array<SpendInputObj>, outputs: Array<OutputObj>, change_addr: Address, protocolParams?: ProtocolParams,
+      selectionPolicy?: SelectionPolicy, selectionSeed?: number
+    ): Promise<FeeEstimate>;
+
+    export type DrainTransactionObj = TransactionObj & {
+      value: string; // amount received by the output
+    };
+
+    // Send all the inputs to a single address, the fee being deducted from the output.
+    export function drain(
+      wallet: WalletObj, inputs: Array<SpendInputObj>, output: Address, protocolParams?: ProtocolParams
+    ): Promise<DrainTransactionObj>;
+
+    export type MigrationInputObj = {
+      ptr: { id: string; index: number };
+      value: OutputObj;
+      // BIP44 addressing of Yoroi wallets or hardened [account, index] of Daedalus random addresses
+      addressing: { account: number; change: number; index: number } | [number, number];
+    };
+    export type MigrationObj = {
+      transactions: Array<{
+        cbor_encoded_tx: HexString; // Shelley transaction with bootstrap witnesses
+        tx_id: HexString;
+        fee: string;
+        value: string;
+        selected_inputs: Array<{ id: string; index: number }>;
+      }>;
+      skipped_inputs: Array<{ id: string; index: number }>; // not worth the fee of their transaction or too large to spend
+      fee: string;
+      value: string;
+    };
+
+    // Move Byron funds to a Shelley address, in as few transactions as the size limit allows.
+    export function migrate(
+      wallet: WalletObj, inputs: Array<MigrationInputObj>, output: Shelley.Address, ttl: number,
+      protocolParams?: ProtocolParams
+    ): Promise<MigrationObj>;
+
     // Move all UTxO to a single address.
     export function move(
-      wallet: DaedalusWalletObj, inputs: Array<MoveInputObj>, output: Address
+      wallet: DaedalusWalletObj, inputs: Array<MoveInputObj>, output: Address, protocolParams?: ProtocolParams
     ): Promise<TransactionObj>;
   }
 
   export namespace RandomAddressChecker {
-    export type AddressCheckerObj = object;
+    // Native object holding the HD payload key, it is never serialized.
+    export type AddressCheckerObj = {
+      check(addresses: Array<Wallet.Address>): Array<{ address: Wallet.Address; addressing: [number, number] }>;
+      checkOne(address: Wallet.Address): [number, number] | null;
+      isDestroyed(): boolean;
+      destroy(): void;
+    };
 
     // Create a random address checker, this will allow validating.
     export function newChecker(xprv: HdWallet.XPrv): Promise<AddressCheckerObj>;